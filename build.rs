@@ -6,6 +6,11 @@ use std::path::PathBuf;
 use std::process::Command;
 
 fn main() {
+    // The resources are only used by the graphical interface
+    if env::var_os("CARGO_FEATURE_GUI").is_none() {
+        return;
+    }
+
     // Get the output directory
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
@@ -3,17 +3,22 @@
 use crate::config::VERSION;
 use crate::window::BootMateWindow;
 
+use bootmate::environment::Environment;
 use libadwaita as adw;
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gettextrs::gettext;
 use gtk::{gio, glib};
+use std::cell::OnceCell;
 
 mod imp {
     use super::*;
 
     #[derive(Debug, Default)]
-    pub struct BootMateApplication {}
+    pub struct BootMateApplication {
+        /// The directories the windows load entries from and save them to
+        pub environment: OnceCell<Environment>,
+    }
 
     #[glib::object_subclass]
     impl ObjectSubclass for BootMateApplication {
@@ -38,7 +43,8 @@ mod imp {
             let window = if let Some(window) = application.active_window() {
                 window
             } else {
-                let window = BootMateWindow::new(&*application);
+                let env = self.environment.get().cloned().unwrap_or_else(Environment::from_env);
+                let window = BootMateWindow::new(&*application, env);
                 window.upcast()
             };
             window.present();
@@ -56,11 +62,13 @@ glib::wrapper! {
 }
 
 impl BootMateApplication {
-    pub fn new(application_id: &str, flags: &gio::ApplicationFlags) -> Self {
-        glib::Object::builder()
+    pub fn new(application_id: &str, flags: &gio::ApplicationFlags, env: Environment) -> Self {
+        let app: Self = glib::Object::builder()
             .property("application-id", application_id)
             .property("flags", flags)
-            .build()
+            .build();
+        app.imp().environment.set(env).expect("environment set twice");
+        app
     }
 
     fn setup_gactions(&self) {
@@ -1,19 +1,1003 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use gtk::glib;
-use std::collections::HashMap;
+use crate::environment::Environment;
+use crate::removed::move_to_removed;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+/// Name of the main group of a Desktop Entry file
+pub const DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+/// Errors of autostart operations
+#[derive(Debug)]
+pub enum AutostartError {
+    /// Access to a file or directory was denied, e.g. by a sandbox
+    PermissionDenied { path: PathBuf },
+    /// The file system containing the path is mounted read-only
+    ReadOnlyFilesystem { path: PathBuf },
+    /// Any other I/O error
+    Io { path: PathBuf, source: io::Error },
+    /// The file is not a valid Desktop Entry, `line` starts at 1
+    Parse { path: PathBuf, line: usize, message: String },
+    /// A required key is missing in the `[Desktop Entry]` group
+    MissingKey { path: PathBuf, key: &'static str },
+    /// An entry with the same desktop file ID already exists
+    NameCollision { path: PathBuf },
+    /// The command can't be parsed
+    InvalidExec { message: String },
+    /// The file was changed by another program since it was loaded
+    Conflict { path: PathBuf },
+}
+
+impl AutostartError {
+    /// Classify an I/O error that occurred while accessing `path`
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        let path = path.to_path_buf();
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => AutostartError::PermissionDenied { path },
+            io::ErrorKind::ReadOnlyFilesystem => AutostartError::ReadOnlyFilesystem { path },
+            _ => AutostartError::Io { path, source },
+        }
+    }
+
+    fn invalid_exec(message: impl Into<String>) -> Self {
+        AutostartError::InvalidExec { message: message.into() }
+    }
+
+    /// The file or directory the error is about, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AutostartError::PermissionDenied { path }
+            | AutostartError::ReadOnlyFilesystem { path }
+            | AutostartError::Io { path, .. }
+            | AutostartError::Parse { path, .. }
+            | AutostartError::MissingKey { path, .. }
+            | AutostartError::NameCollision { path }
+            | AutostartError::Conflict { path } => Some(path),
+            AutostartError::InvalidExec { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for AutostartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutostartError::PermissionDenied { path } => {
+                write!(f, "Permission denied: {}", path.display())
+            }
+            AutostartError::ReadOnlyFilesystem { path } => {
+                write!(f, "Read-only file system: {}", path.display())
+            }
+            AutostartError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            AutostartError::Parse { path, line, message } => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            AutostartError::MissingKey { path, key } => {
+                write!(f, "{}: Missing {} field", path.display(), key)
+            }
+            AutostartError::NameCollision { path } => {
+                write!(f, "An entry already exists at {}", path.display())
+            }
+            AutostartError::InvalidExec { message } => write!(f, "Invalid command: {}", message),
+            AutostartError::Conflict { path } => {
+                write!(f, "{} was changed by another program", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AutostartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AutostartError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A syntax error in a Desktop Entry file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Line number starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+/// How many symlinks are followed at most, like the kernel does, in case of
+/// symlink loops
+const MAX_SYMLINKS: usize = 40;
+
+/// Follow a chain of symlinks to the file it finally points to.
+///
+/// Without a `root`, only the last part of the path is followed, the system
+/// resolves the directories. With a `root`, every part of the path is
+/// resolved inside it: absolute links point into the root rather than to the
+/// running system, and `..` never leaves it. Paths outside of the root are
+/// returned as they are.
+///
+/// The target doesn't need to exist, so dangling links resolve to the path
+/// the file would be created at.
+pub(crate) fn resolve_symlinks(path: &Path, root: Option<&Path>) -> PathBuf {
+    let Some(root) = root else {
+        let mut path = path.to_path_buf();
+        for _ in 0..MAX_SYMLINKS {
+            match fs::read_link(&path) {
+                Ok(target) => {
+                    path = match path.parent() {
+                        Some(parent) if target.is_relative() => parent.join(target),
+                        _ => target,
+                    };
+                }
+                Err(_) => break,
+            }
+        }
+        return path;
+    };
+
+    let Ok(relative) = path.strip_prefix(root) else {
+        return path.to_path_buf();
+    };
+
+    let mut resolved = root.to_path_buf();
+    // The parts left to resolve, the next one last
+    let mut parts: Vec<OsString> = relative.iter().rev().map(OsStr::to_os_string).collect();
+    let mut links = 0;
+
+    while let Some(part) = parts.pop() {
+        if part == ".." {
+            if resolved != root {
+                resolved.pop();
+            }
+            continue;
+        }
+        if part == "." || part == "/" {
+            continue;
+        }
+
+        let next = resolved.join(&part);
+        match fs::read_link(&next) {
+            Ok(target) if links < MAX_SYMLINKS => {
+                links += 1;
+                if target.is_absolute() {
+                    resolved = root.to_path_buf();
+                }
+                parts.extend(target.iter().rev().map(OsStr::to_os_string));
+            }
+            _ => resolved = next,
+        }
+    }
+
+    resolved
+}
+
+/// Whether `path` is an executable file, resolving it inside `root` if given
+pub(crate) fn is_executable(path: &Path, root: Option<&Path>) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(resolve_symlinks(path, root))
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Replace the content of a file atomically.
+///
+/// The content is written to a temporary file in the same directory, synced
+/// to disk and renamed over the target, so readers and crashes never see a
+/// partially written file. The permissions of an existing file are kept, and
+/// symlinks are written through to the file they point to.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), AutostartError> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let target = resolve_symlinks(path, None);
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let permissions = match fs::metadata(&target) {
+        Ok(metadata) => metadata.permissions(),
+        Err(_) => fs::Permissions::from_mode(0o644),
+    };
+
+    let temp_path = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write_temp = || -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temp_path)?;
+        file.write_all(content)?;
+        file.set_permissions(permissions.clone())?;
+        file.sync_all()
+    };
+
+    if let Err(e) = write_temp().and_then(|_| fs::rename(&temp_path, &target)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(AutostartError::io(&target, e));
+    }
+
+    // Make sure the rename itself is on disk
+    if let Ok(dir) = fs::File::open(&dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Look up a program like the session does, either as a path or in `$PATH`.
+///
+/// Returns the path of the executable if it exists.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path, None).then_some(path);
+    }
+
+    let search_path = std::env::var_os("PATH")?;
+    std::env::split_paths(&search_path)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path, None))
+}
+
+/// Get the names of the current desktop environment (`$XDG_CURRENT_DESKTOP`)
+pub fn current_desktops() -> Vec<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Get the name and autostart command of all applications in the
+/// `applications` directories of `env`, sorted by name
+pub fn installed_applications(env: &Environment) -> Vec<(String, String)> {
+    // Applications in more important directories override the others
+    let mut seen_ids = HashSet::new();
+    let paths: Vec<PathBuf> = env
+        .applications_dirs()
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| {
+            let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            paths.sort();
+            paths
+        })
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("desktop"))
+        .filter(|path| seen_ids.insert(path.file_name().map(|name| name.to_os_string())))
+        .collect();
+
+    let mut applications: Vec<(String, String)> = paths
+        .iter()
+        .filter_map(|path| AutostartEntry::from_file(env, path).ok())
+        .map(|entry| {
+            // Field codes like %u make no sense for autostart
+            let command = entry.autostart_exec().unwrap_or_else(|_| entry.exec.clone());
+            (entry.name, command)
+        })
+        .collect();
+
+    applications.sort_by(|a, b| a.0.cmp(&b.0));
+    applications
+}
+
+/// Get the locale names to look up localized keys with, most specific first.
+///
+/// Follows the Desktop Entry spec: for `lang_COUNTRY@MODIFIER` the lookup
+/// order is `lang_COUNTRY@MODIFIER`, `lang_COUNTRY`, `lang@MODIFIER`, `lang`.
+/// The encoding part of the locale is ignored.
+pub fn current_locales() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+
+    locale_variants(&locale)
+}
+
+/// Expand a POSIX locale name into the lookup chain for localized keys
+fn locale_variants(locale: &str) -> Vec<String> {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let rest = rest.split('.').next().unwrap_or_default();
+    let (lang, country) = match rest.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (rest, None),
+    };
+
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut variants = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        variants.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        variants.push(format!("{}_{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        variants.push(format!("{}@{}", lang, modifier));
+    }
+    variants.push(lang.to_string());
+    variants
+}
+
+/// Unescape a Desktop Entry string value (`\s`, `\n`, `\t`, `\r` and `\\`).
+///
+/// Unknown escape sequences are kept as they are.
+pub fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+/// Escape a string so it can be stored as a Desktop Entry value
+pub fn escape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            // Leading whitespace would be stripped by parsers
+            ' ' if index == 0 => result.push_str("\\s"),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Split a `;`-separated string list value into its unescaped elements
+pub fn parse_string_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            ';' => items.push(unescape_value(&std::mem::take(&mut current))),
+            c => current.push(c),
+        }
+    }
+
+    // The trailing separator is optional
+    if !current.is_empty() {
+        items.push(unescape_value(&current));
+    }
+
+    items
+}
+
+/// Join string list elements into a `;`-separated and escaped value
+pub fn format_string_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("{};", escape_value(item).replace(';', "\\;")))
+        .collect()
+}
+
+/// Characters that require an Exec argument to be quoted
+const EXEC_RESERVED_CHARS: &[char] = &[
+    ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')',
+    '`',
+];
+
+/// Split an (already unescaped) Exec value into its arguments.
+///
+/// Arguments are separated by spaces and may be quoted with double quotes,
+/// inside of which `"`, `` ` ``, `$` and `\` are escaped with a backslash.
+/// Reserved characters like `'` or `|` are only allowed inside quotes.
+/// Field codes are left as they are, see [`expand_field_codes`].
+pub fn parse_exec(exec: &str) -> Result<Vec<String>, AutostartError> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_argument {
+                    args.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            '"' => {
+                in_argument = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => current.push(escaped),
+                            Some(other) => {
+                                return Err(AutostartError::invalid_exec(format!(
+                                    "Invalid escape sequence \"\\{}\" in quoted argument",
+                                    other
+                                )));
+                            }
+                            None => return Err(AutostartError::invalid_exec("Unterminated quoted argument")),
+                        },
+                        Some(other) => current.push(other),
+                        None => return Err(AutostartError::invalid_exec("Unterminated quoted argument")),
+                    }
+                }
+            }
+            c if EXEC_RESERVED_CHARS.contains(&c) => {
+                return Err(AutostartError::invalid_exec(format!(
+                    "Reserved character \"{}\" outside of a quoted argument",
+                    c
+                )));
+            }
+            c => {
+                in_argument = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_argument {
+        args.push(current);
+    }
+
+    if args.is_empty() {
+        return Err(AutostartError::invalid_exec("The command is empty"));
+    }
+
+    Ok(args)
+}
+
+/// Quote a single argument for use in an Exec value, if needed.
+///
+/// Literal `%` characters are doubled so they aren't taken as field codes.
+pub fn quote_exec_arg(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    if !arg.is_empty() && !arg.contains(EXEC_RESERVED_CHARS) {
+        return arg;
+    }
+
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Join arguments into an Exec value, quoting them where necessary
+pub fn format_exec(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote_exec_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Expand the field codes of parsed Exec arguments for a launch without files.
+///
+/// File and URL codes (`%f`, `%F`, `%u`, `%U`) and the deprecated codes are
+/// dropped since autostart launches never pass any. `%i` becomes `--icon <Icon>`,
+/// `%c` the name, `%k` the location of the desktop file and `%%` a single `%`.
+pub fn expand_field_codes(
+    args: &[String],
+    name: &str,
+    icon: Option<&str>,
+    file_path: &Path,
+) -> Result<Vec<String>, AutostartError> {
+    let mut expanded = Vec::new();
+
+    for arg in args {
+        if arg == "%i" {
+            if let Some(icon) = icon {
+                expanded.push("--icon".to_string());
+                expanded.push(icon.to_string());
+            }
+            continue;
+        }
+
+        let mut result = String::new();
+        let mut had_field_code = false;
+        let mut chars = arg.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => result.push('%'),
+                Some('f' | 'F' | 'u' | 'U' | 'd' | 'D' | 'n' | 'N' | 'v' | 'm') => {
+                    had_field_code = true;
+                }
+                Some('i') => result.push_str(icon.unwrap_or_default()),
+                Some('c') => result.push_str(name),
+                Some('k') => result.push_str(&file_path.to_string_lossy()),
+                Some(other) => {
+                    return Err(AutostartError::invalid_exec(format!(
+                        "Unknown field code \"%{}\"",
+                        other
+                    )));
+                }
+                None => {
+                    return Err(AutostartError::invalid_exec(
+                        "Incomplete field code at the end of an argument",
+                    ));
+                }
+            }
+        }
+
+        // Arguments that only consisted of a removed field code are dropped
+        if !(had_field_code && result.is_empty()) {
+            expanded.push(result);
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// A single line of a Desktop Entry file
+#[derive(Debug, Clone, PartialEq)]
+enum Line {
+    /// Blank lines, comments and anything else we don't interpret, kept as-is
+    Verbatim(String),
+    /// A group header such as `[Desktop Entry]`
+    Group(String),
+    /// A `Key=Value` pair, `raw` is the original line used when writing it back
+    Entry { key: String, value: String, raw: String },
+}
+
+/// Lossless in-memory representation of a Desktop Entry (key file).
+///
+/// Every line of the original file is kept, including comments, unknown keys,
+/// localized keys and additional groups such as `[Desktop Action ...]`.
+/// Writing the file back only changes the lines of keys that were modified.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DesktopFile {
+    lines: Vec<Line>,
+    /// Whether lines end with `\r\n` instead of `\n`, as in the first line
+    crlf: bool,
+}
+
+impl DesktopFile {
+    /// Parse the content of a Desktop Entry file
+    pub fn parse(content: &str) -> Result<Self, ParseError> {
+        let mut lines = Vec::new();
+        let mut in_group = false;
+
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            let error = |message: &str| ParseError {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            if trimmed.starts_with('[') {
+                if !trimmed.ends_with(']') || trimmed.len() < 3 {
+                    return Err(error("Invalid group header"));
+                }
+                in_group = true;
+                lines.push(Line::Group(trimmed[1..trimmed.len() - 1].to_string()));
+                continue;
+            }
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                lines.push(Line::Verbatim(line.to_string()));
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("Expected a key-value pair, a group header or a comment"));
+            };
+            if !in_group {
+                return Err(error("Key-value pair outside of a group"));
+            }
+            if key.trim().is_empty() {
+                return Err(error("Empty key"));
+            }
+
+            lines.push(Line::Entry {
+                key: key.trim().to_string(),
+                value: value.trim_start().to_string(),
+                raw: line.to_string(),
+            });
+        }
+
+        let crlf = content
+            .find('\n')
+            .is_some_and(|end| content[..end].ends_with('\r'));
+
+        Ok(DesktopFile { lines, crlf })
+    }
+
+    /// Iterate over the (group, line index) pairs of all lines
+    fn groups(&self) -> impl Iterator<Item = (Option<&str>, usize)> {
+        let mut current = None;
+        self.lines.iter().enumerate().map(move |(index, line)| {
+            if let Line::Group(name) = line {
+                current = Some(name.as_str());
+            }
+            (current, index)
+        })
+    }
+
+    /// Find the line index of `key` inside `group`
+    fn find(&self, group: &str, key: &str) -> Option<usize> {
+        self.groups().find_map(|(current, index)| match &self.lines[index] {
+            Line::Entry { key: k, .. } if current == Some(group) && k == key => Some(index),
+            _ => None,
+        })
+    }
+
+    /// Get the unescaped value of a localized `key` in `group` for the first
+    /// matching locale, falling back to the unlocalized key.
+    ///
+    /// Returns the full key the value was found under, e.g. `Name[de]`.
+    pub fn get_localized(&self, group: &str, key: &str, locales: &[String]) -> Option<(String, String)> {
+        locales
+            .iter()
+            .map(|locale| format!("{}[{}]", key, locale))
+            .chain(std::iter::once(key.to_string()))
+            .find_map(|full_key| self.get_string(group, &full_key).map(|value| (full_key, value)))
+    }
+
+    /// Whether any translation of `key` exists in `group`
+    fn has_translations(&self, group: &str, key: &str) -> bool {
+        let prefix = format!("{}[", key);
+        self.groups().any(|(current, index)| match &self.lines[index] {
+            Line::Entry { key: k, .. } => current == Some(group) && k.starts_with(&prefix),
+            _ => false,
+        })
+    }
+
+    /// Get the key to write a localized value for the current language to.
+    ///
+    /// If the value currently shown comes from a translation, that translation
+    /// is updated. If the file is translated but has no translation for our
+    /// language, a new one is added for the language without country, so other
+    /// translations and the untranslated value stay as they are. Files without
+    /// any translations just use the plain key.
+    fn localized_key(&self, group: &str, key: &str, locales: &[String]) -> String {
+        match self.get_localized(group, key, locales) {
+            Some((full_key, _)) if full_key != key => full_key,
+            _ => match locales.last() {
+                Some(lang) if self.has_translations(group, key) => format!("{}[{}]", key, lang),
+                _ => key.to_string(),
+            },
+        }
+    }
+
+    /// Set the value of a localized key for the current language, see
+    /// [`Self::localized_key`]. Nothing is written if the value shown for the
+    /// current language is already `value`, and `None` removes it.
+    pub fn set_localized(&mut self, group: &str, key: &str, locales: &[String], value: Option<&str>) {
+        if self.get_localized(group, key, locales).map(|(_, v)| v).as_deref() == value {
+            return;
+        }
+
+        let full_key = self.localized_key(group, key, locales);
+        match value {
+            Some(value) => self.set_string(group, &full_key, value),
+            None => self.remove(group, &full_key),
+        }
+    }
+
+    /// Whether the file contains the given group
+    pub fn has_group(&self, group: &str) -> bool {
+        self.lines
+            .iter()
+            .any(|line| matches!(line, Line::Group(name) if name == group))
+    }
+
+    /// Get all keys of `group` with their raw values, in file order
+    pub fn entries(&self, group: &str) -> Vec<(&str, &str)> {
+        self.groups()
+            .filter_map(|(current, index)| match &self.lines[index] {
+                Line::Entry { key, value, .. } if current == Some(group) => {
+                    Some((key.as_str(), value.as_str()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the raw value of `key` in `group`
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.find(group, key).and_then(|index| match &self.lines[index] {
+            Line::Entry { value, .. } => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Set the raw value of `key` in `group`.
+    ///
+    /// Existing keys are updated in place and left untouched if the value
+    /// doesn't change. New keys are appended after the last key of the group,
+    /// and the group is created at the end of the file if it doesn't exist.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        let new_line = Line::Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            raw: format!("{}={}", key, value),
+        };
+
+        if let Some(index) = self.find(group, key) {
+            if let Line::Entry { value: old, .. } = &self.lines[index] {
+                if old != value {
+                    self.lines[index] = new_line;
+                }
+            }
+            return;
+        }
+
+        // Insert after the last key (or the header) of the group, so trailing
+        // blank lines and comments stay in front of the next group
+        let insert_at = self
+            .groups()
+            .filter(|(current, index)| {
+                *current == Some(group) && !matches!(self.lines[*index], Line::Verbatim(_))
+            })
+            .map(|(_, index)| index + 1)
+            .last();
+
+        match insert_at {
+            Some(index) => self.lines.insert(index, new_line),
+            None => {
+                if matches!(self.lines.last(), Some(line) if *line != Line::Verbatim(String::new())) {
+                    self.lines.push(Line::Verbatim(String::new()));
+                }
+                self.lines.push(Line::Group(group.to_string()));
+                self.lines.push(new_line);
+            }
+        }
+    }
+
+    /// Get the unescaped string value of `key` in `group`
+    pub fn get_string(&self, group: &str, key: &str) -> Option<String> {
+        self.get(group, key).map(unescape_value)
+    }
+
+    /// Set `key` in `group` to an escaped string value.
+    ///
+    /// Nothing is written if the unescaped value doesn't change, so values
+    /// using a different but equivalent escaping are kept as they are.
+    pub fn set_string(&mut self, group: &str, key: &str, value: &str) {
+        if self.get_string(group, key).as_deref() != Some(value) {
+            self.set(group, key, &escape_value(value));
+        }
+    }
+
+    /// Get the elements of the string list `key` in `group`
+    pub fn get_string_list(&self, group: &str, key: &str) -> Option<Vec<String>> {
+        self.get(group, key).map(parse_string_list)
+    }
+
+    /// Set `key` in `group` to a string list, unless it already has these elements
+    pub fn set_string_list(&mut self, group: &str, key: &str, items: &[String]) {
+        if self.get_string_list(group, key).as_deref() != Some(items) {
+            self.set(group, key, &format_string_list(items));
+        }
+    }
+
+    /// Remove `key` from `group`
+    pub fn remove(&mut self, group: &str, key: &str) {
+        if let Some(index) = self.find(group, key) {
+            self.lines.remove(index);
+        }
+    }
+}
+
+impl fmt::Display for DesktopFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        for line in &self.lines {
+            match line {
+                Line::Verbatim(text) | Line::Entry { raw: text, .. } => write!(f, "{}{}", text, newline)?,
+                Line::Group(name) => write!(f, "[{}]{}", name, newline)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state of a file when it was loaded, to detect changes by other programs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStamp {
+    modified: Option<std::time::SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileStamp {
+    fn new(content: &[u8], modified: Option<std::time::SystemTime>) -> Self {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        content.hash(&mut hasher);
+
+        FileStamp {
+            modified,
+            len: content.len() as u64,
+            hash: hasher.finish(),
+        }
+    }
+
+    /// Whether the file at `path` still has the content it had when this
+    /// stamp was taken. Files that were only touched count as unchanged.
+    fn matches(&self, path: &Path) -> bool {
+        let Ok(metadata) = fs::metadata(path) else {
+            return false;
+        };
+        if metadata.modified().ok() == self.modified && metadata.len() == self.len {
+            return true;
+        }
+
+        fs::read(path)
+            .map(|content| FileStamp::new(&content, None).hash == self.hash)
+            .unwrap_or(false)
+    }
+}
+
+/// The content of a file at some point, used to undo changes
 #[derive(Debug, Clone)]
+pub struct FileSnapshot {
+    path: PathBuf,
+    /// `None` if the file didn't exist
+    content: Option<Vec<u8>>,
+}
+
+impl FileSnapshot {
+    /// Record the current content of the file at `path`
+    pub fn take(path: &Path) -> Result<Self, AutostartError> {
+        let content = match fs::read(path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(AutostartError::io(path, e)),
+        };
+
+        Ok(FileSnapshot {
+            path: path.to_path_buf(),
+            content,
+        })
+    }
+
+    /// Put the recorded content back, removing the file if it didn't exist
+    pub fn restore(&self) -> Result<(), AutostartError> {
+        match &self.content {
+            Some(content) => write_atomic(&self.path, content),
+            None => match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    Err(AutostartError::io(&self.path, e))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// A key whose value differs between two versions of a Desktop Entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyChange {
+    pub key: String,
+    /// Raw value in the original file, `None` if the key was added
+    pub old_value: Option<String>,
+    /// Raw value in the changed file, `None` if the key was removed
+    pub new_value: Option<String>,
+}
+
+/// Where an autostart entry was loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntrySource {
+    /// The user's autostart directory in `$XDG_CONFIG_HOME`
+    User,
+    /// One of the system-wide autostart directories
+    System,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct AutostartEntry {
+    /// Name in the current language
     pub name: String,
     pub exec: String,
     pub icon: Option<String>,
+    /// Comment in the current language
     pub comment: Option<String>,
+    /// Program that must be installed for the entry to be used (`TryExec`)
+    pub try_exec: Option<String>,
+    /// Desktop environments this entry is exclusively started in (`OnlyShowIn`)
+    pub only_show_in: Vec<String>,
+    /// Desktop environments this entry is not started in (`NotShowIn`)
+    pub not_show_in: Vec<String>,
     pub enabled: bool,
+    /// Whether the entry is treated as deleted (`Hidden`), e.g. a user file
+    /// hiding a system entry
+    pub hidden: bool,
+    /// Seconds to wait after login before starting (`X-GNOME-Autostart-Delay`)
+    pub delay: Option<u32>,
     pub file_path: PathBuf,
-    pub is_user_entry: bool,
+    pub source: EntrySource,
+    /// Lower-priority files with the same desktop file ID that this entry overrides
+    pub shadows: Vec<PathBuf>,
+    /// The directories the entry was loaded from, changes are written there
+    env: Arc<Environment>,
+    /// The file this entry was read from, used to preserve unknown keys on write
+    desktop_file: DesktopFile,
+    /// State of `file_path` at load time, `None` for new entries
+    stamp: Option<FileStamp>,
+}
+
+/// An autostart file that couldn't be loaded
+#[derive(Debug)]
+pub struct LoadProblem {
+    pub path: PathBuf,
+    pub source: EntrySource,
+    pub error: AutostartError,
+    /// Whether repairing the file in memory succeeded when it was loaded
+    repairable: bool,
+}
+
+impl LoadProblem {
+    /// Whether [`AutostartEntry::repair`] is able to fix the file, e.g. it
+    /// can't if the file has no command
+    pub fn is_repairable(&self) -> bool {
+        self.repairable
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,77 +1009,313 @@ pub enum SandboxType {
 #[derive(Debug, Clone)]
 pub struct DirectoryAccess {
     pub user_autostart: bool,
-    pub etc_xdg_autostart: bool,
-    pub usr_share_gnome_autostart: bool,
+    /// Every system autostart directory and whether it can be read
+    pub system_autostart: Vec<(PathBuf, bool)>,
     pub usr_share_applications: bool,
     pub sandbox_type: SandboxType,
 }
 
 impl AutostartEntry {
+    /// Create a new user entry that doesn't exist on disk yet, with a file
+    /// name derived from its name
+    pub fn new(env: &Environment, name: &str, exec: &str) -> Self {
+        let mut desktop_file = DesktopFile::default();
+        desktop_file.set(DESKTOP_ENTRY_GROUP, "Type", "Application");
+        desktop_file.set(DESKTOP_ENTRY_GROUP, "Terminal", "false");
+
+        AutostartEntry {
+            name: name.to_string(),
+            exec: exec.to_string(),
+            icon: None,
+            comment: None,
+            try_exec: None,
+            only_show_in: Vec::new(),
+            not_show_in: Vec::new(),
+            enabled: true,
+            hidden: false,
+            delay: None,
+            file_path: env.user_entry_path(name),
+            source: EntrySource::User,
+            shadows: Vec::new(),
+            env: Arc::new(env.clone()),
+            desktop_file,
+            stamp: None,
+        }
+    }
+
     /// Parse a .desktop file into an AutostartEntry
-    pub fn from_file(path: &Path) -> Result<Self, String> {
-        let content = fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-
-        let mut name = None;
-        let mut exec = None;
-        let mut icon = None;
-        let mut comment = None;
-        let mut enabled = true;
-        let mut in_desktop_entry = false;
-
-        for line in content.lines() {
-            let line = line.trim();
-
-            if line.starts_with('[') {
-                in_desktop_entry = line == "[Desktop Entry]";
-                continue;
-            }
+    pub fn from_file(env: &Environment, path: &Path) -> Result<Self, AutostartError> {
+        let source = if path.parent() == Some(env.user_autostart_dir().as_path()) {
+            EntrySource::User
+        } else {
+            EntrySource::System
+        };
 
-            if !in_desktop_entry || line.is_empty() || line.starts_with('#') {
-                continue;
+        Self::load(&Arc::new(env.clone()), path, source)
+    }
+
+    /// Parse a .desktop file found in an autostart directory of the given source
+    fn load(env: &Arc<Environment>, path: &Path, source: EntrySource) -> Result<Self, AutostartError> {
+        let real_path = env.resolve(path);
+        let bytes = fs::read(&real_path).map_err(|e| AutostartError::io(path, e))?;
+        let modified = fs::metadata(&real_path).and_then(|metadata| metadata.modified()).ok();
+        let stamp = FileStamp::new(&bytes, modified);
+        let content = String::from_utf8(bytes).map_err(|e| {
+            let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+            AutostartError::Parse {
+                path: path.to_path_buf(),
+                line: valid.iter().filter(|b| **b == b'\n').count() + 1,
+                message: "Invalid UTF-8".to_string(),
             }
+        })?;
 
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim();
-                let value = value.trim();
+        let desktop_file = DesktopFile::parse(&content).map_err(|e| AutostartError::Parse {
+            path: path.to_path_buf(),
+            line: e.line,
+            message: e.message,
+        })?;
 
-                match key {
-                    "Name" => name = Some(value.to_string()),
-                    "Exec" => exec = Some(value.to_string()),
-                    "Icon" => icon = Some(value.to_string()),
-                    "Comment" => comment = Some(value.to_string()),
-                    "X-GNOME-Autostart-enabled" => {
-                        enabled = value.to_lowercase() != "false";
-                    }
-                    "Hidden" => {
-                        if value.to_lowercase() == "true" {
-                            enabled = false;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
+        let mut entry = Self::from_desktop_file(env, desktop_file, path, source)?;
+        entry.stamp = Some(stamp);
+        Ok(entry)
+    }
+
+    /// Create an entry from a parsed file located at `path`
+    fn from_desktop_file(
+        env: &Arc<Environment>,
+        desktop_file: DesktopFile,
+        path: &Path,
+        source: EntrySource,
+    ) -> Result<Self, AutostartError> {
+        let get = |key| desktop_file.get_string(DESKTOP_ENTRY_GROUP, key);
+        let get_list = |key| {
+            desktop_file
+                .get_string_list(DESKTOP_ENTRY_GROUP, key)
+                .unwrap_or_default()
+        };
+        let locales = current_locales();
+        let get_localized = |key| {
+            desktop_file
+                .get_localized(DESKTOP_ENTRY_GROUP, key, &locales)
+                .map(|(_, value)| value)
+        };
+
+        let hidden = get("Hidden")
+            .map(|value| value.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        // Files that only hide another entry may contain nothing else, their
+        // name and command are taken from the hidden file once all are loaded
+        let required = |key, value: Option<String>| match value {
+            Some(value) => Ok(value),
+            None if hidden => Ok(String::new()),
+            None => Err(AutostartError::MissingKey {
+                path: path.to_path_buf(),
+                key,
+            }),
+        };
+        let name = required("Name", get_localized("Name"))?;
+        let exec = required("Exec", get("Exec"))?;
+        let icon = get("Icon");
+        let comment = get_localized("Comment");
+        let try_exec = get("TryExec");
+        let only_show_in = get_list("OnlyShowIn");
+        let not_show_in = get_list("NotShowIn");
 
-        let name = name.ok_or("Missing Name field")?;
-        let exec = exec.ok_or("Missing Exec field")?;
+        let enabled = get("X-GNOME-Autostart-enabled")
+            .map(|value| value.to_lowercase() != "false")
+            .unwrap_or(true);
 
-        let is_user_entry = path.to_string_lossy().contains(".config/autostart");
+        let delay = get("X-GNOME-Autostart-Delay")
+            .and_then(|value| value.trim().parse().ok())
+            .filter(|delay| *delay > 0);
 
         Ok(AutostartEntry {
             name,
             exec,
             icon,
             comment,
+            try_exec,
+            only_show_in,
+            not_show_in,
             enabled,
+            hidden,
+            delay,
             file_path: path.to_path_buf(),
-            is_user_entry,
+            source,
+            shadows: Vec::new(),
+            env: Arc::clone(env),
+            desktop_file,
+            stamp: None,
         })
     }
 
+    /// Get the desktop file ID of this entry.
+    ///
+    /// Autostart directories are flat, so this is the file name. Files with
+    /// the same ID in more important directories override the others.
+    pub fn desktop_id(&self) -> String {
+        self.file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Whether this entry is started in a session of the given desktops,
+    /// according to its `OnlyShowIn` and `NotShowIn` keys.
+    ///
+    /// As in the spec, the desktops are checked in order and the first one
+    /// listed in either key decides. If none matches, the entry is only started
+    /// if it has no `OnlyShowIn` key.
+    pub fn shown_in(&self, desktops: &[String]) -> bool {
+        let contains = |list: &[String], desktop: &str| {
+            list.iter().any(|item| item.eq_ignore_ascii_case(desktop))
+        };
+
+        for desktop in desktops {
+            if contains(&self.only_show_in, desktop) {
+                return true;
+            }
+            if contains(&self.not_show_in, desktop) {
+                return false;
+            }
+        }
+
+        self.only_show_in.is_empty()
+    }
+
+    /// Whether this entry is started in the current desktop environment
+    pub fn shown_in_current_desktop(&self) -> bool {
+        self.shown_in(&current_desktops())
+    }
+
+    /// Whether this entry is enabled and actually started in the current session
+    pub fn is_effectively_enabled(&self) -> bool {
+        self.enabled && !self.hidden && self.shown_in_current_desktop()
+    }
+
+    /// Get the program of this entry that is not installed, if any.
+    ///
+    /// This is the `TryExec` program if the entry has one, otherwise the first
+    /// argument of the command. Inside a Flatpak sandbox the programs of the
+    /// host can't be seen, so nothing is reported there.
+    pub fn missing_program(&self) -> Option<String> {
+        if Self::detect_sandbox() != SandboxType::None {
+            return None;
+        }
+
+        let program = match &self.try_exec {
+            Some(try_exec) => try_exec.clone(),
+            None => parse_exec(&self.exec).ok()?.into_iter().next()?,
+        };
+
+        self.env.find_program(&program).is_none().then_some(program)
+    }
+
+    /// Whether the program started by this entry is not installed
+    pub fn is_program_missing(&self) -> bool {
+        self.missing_program().is_some()
+    }
+
+    /// Get the directories this entry was loaded from
+    pub fn environment(&self) -> &Environment {
+        &self.env
+    }
+
+    /// Whether this entry lives in the user's autostart directory
+    pub fn is_user_entry(&self) -> bool {
+        self.source == EntrySource::User
+    }
+
+    /// Whether this is a user file overriding a system entry with the same ID
+    pub fn is_override(&self) -> bool {
+        self.is_user_entry() && !self.shadows.is_empty()
+    }
+
+    /// Whether deleting this entry removes its file, rather than hiding it
+    /// with a user file
+    pub fn is_removed_on_delete(&self) -> bool {
+        self.is_user_entry() && !self.is_override()
+    }
+
+    /// Get the keys of the `[Desktop Entry]` group this override changes
+    /// compared to the system entry it overrides
+    pub fn override_changes(&self) -> Result<Vec<KeyChange>, AutostartError> {
+        let Some(system_path) = self.shadows.first() else {
+            return Ok(Vec::new());
+        };
+        let system = Self::load(&self.env, system_path, EntrySource::System)?;
+
+        let old_entries = system.desktop_file.entries(DESKTOP_ENTRY_GROUP);
+        let new_entries = self.desktop_file.entries(DESKTOP_ENTRY_GROUP);
+        let find = |entries: &[(&str, &str)], key: &str| {
+            entries
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| value.to_string())
+        };
+
+        // Changed and removed keys in the order of the system file, then added ones
+        let keys = old_entries.iter().map(|(key, _)| *key).chain(
+            new_entries
+                .iter()
+                .map(|(key, _)| *key)
+                .filter(|key| find(&old_entries, key).is_none()),
+        );
+
+        Ok(keys
+            .map(|key| KeyChange {
+                key: key.to_string(),
+                old_value: find(&old_entries, key),
+                new_value: find(&new_entries, key),
+            })
+            .filter(|change| change.old_value != change.new_value)
+            .collect())
+    }
+
+    /// Make a hidden entry visible again. Hidden system entries are restored
+    /// by removing the user file hiding them.
+    pub fn unhide(&self) -> Result<(), AutostartError> {
+        if self.is_override() {
+            return self.reset_to_system_default();
+        }
+
+        let mut entry = self.clone();
+        entry.hidden = false;
+        entry.save()?;
+        Ok(())
+    }
+
+    /// Remove the user file overriding a system entry, so the system entry
+    /// is used as it is again
+    pub fn reset_to_system_default(&self) -> Result<(), AutostartError> {
+        if !self.is_override() {
+            return Ok(());
+        }
+
+        self.check_unchanged(&self.file_path)?;
+        fs::remove_file(&self.file_path).map_err(|e| AutostartError::io(&self.file_path, e))
+    }
+
+    /// Check that the command of this entry can be parsed
+    pub fn validate(&self) -> Result<(), AutostartError> {
+        let args = parse_exec(&self.exec)?;
+        expand_field_codes(&args, &self.name, self.icon.as_deref(), &self.file_path)?;
+        Ok(())
+    }
+
+    /// Get the command with all field codes expanded, as it is run at login.
+    ///
+    /// Used when turning an application into an autostart entry, so e.g.
+    /// `firefox %u` becomes `firefox`.
+    pub fn autostart_exec(&self) -> Result<String, AutostartError> {
+        let args = parse_exec(&self.exec)?;
+        let args = expand_field_codes(&args, &self.name, self.icon.as_deref(), &self.file_path)?;
+        Ok(format_exec(&args))
+    }
+
     /// Detect which sandbox environment we're running in
-    fn detect_sandbox() -> SandboxType {
+    pub fn detect_sandbox() -> SandboxType {
         if std::env::var("FLATPAK_ID").is_ok() {
             SandboxType::Flatpak
         } else {
@@ -104,164 +1324,672 @@ impl AutostartEntry {
     }
 
     /// Check which autostart directories are accessible
-    pub fn check_directory_access() -> DirectoryAccess {
+    pub fn check_directory_access(env: &Environment) -> DirectoryAccess {
         let sandbox_type = Self::detect_sandbox();
 
-        let mut access = DirectoryAccess {
-            user_autostart: false,
-            etc_xdg_autostart: false,
-            usr_share_gnome_autostart: false,
-            usr_share_applications: false,
+        let user_autostart = env.user_autostart_dir();
+
+        DirectoryAccess {
+            user_autostart: fs::read_dir(&user_autostart).is_ok()
+                || fs::create_dir_all(&user_autostart).is_ok(),
+            system_autostart: env
+                .system_autostart_dirs()
+                .into_iter()
+                .map(|dir| {
+                    let readable = fs::read_dir(&dir).is_ok();
+                    (dir, readable)
+                })
+                .collect(),
+            usr_share_applications: env
+                .applications_dirs()
+                .iter()
+                .any(|dir| fs::read_dir(dir).is_ok()),
             sandbox_type,
-        };
+        }
+    }
+
+    /// Get all autostart entries from system and user directories.
+    ///
+    /// A file overrides all files with the same desktop file ID in less
+    /// important directories, these are recorded in [`Self::shadows`].
+    pub fn load_all(env: &Environment) -> Vec<Self> {
+        Self::load_all_with_problems(env).0
+    }
+
+    /// Get all autostart entries like [`Self::load_all`], together with the
+    /// files that couldn't be loaded
+    pub fn load_all_with_problems(env: &Environment) -> (Vec<Self>, Vec<LoadProblem>) {
+        let shared_env = Arc::new(env.clone());
+        let mut entries: Vec<Self> = Vec::new();
+        let mut problems = Vec::new();
+        // Desktop file ID -> index of the entry using it, `None` if the
+        // overriding file couldn't be parsed
+        let mut seen_ids: HashMap<String, Option<usize>> = HashMap::new();
+
+        // The user directory comes first, it takes precedence over all others
+        for (autostart_dir, source) in env.autostart_dirs() {
+            let Ok(dir_entries) = fs::read_dir(&autostart_dir) else {
+                continue;
+            };
+
+            let mut paths: Vec<PathBuf> = dir_entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("desktop"))
+                .collect();
+            paths.sort();
 
-        // Check user autostart directory
-        if let Some(user_dir) = glib::user_config_dir().as_path().to_str() {
-            let user_autostart = PathBuf::from(user_dir).join("autostart");
-            access.user_autostart = fs::read_dir(&user_autostart).is_ok() ||
-                                   fs::create_dir_all(&user_autostart).is_ok();
-        }
-
-        // Check system directories
-        access.etc_xdg_autostart = fs::read_dir("/etc/xdg/autostart").is_ok();
-        access.usr_share_gnome_autostart = fs::read_dir("/usr/share/gnome/autostart").is_ok();
-        access.usr_share_applications = fs::read_dir("/usr/share/applications").is_ok();
-
-        access
-    }
-
-    /// Get all autostart entries from system and user directories
-    pub fn load_all() -> Vec<Self> {
-        let mut entries = Vec::new();
-        let mut seen_names = HashMap::new();
-
-        // User autostart directory (takes precedence)
-        if let Some(user_dir) = glib::user_config_dir().as_path().to_str() {
-            let user_autostart = PathBuf::from(user_dir).join("autostart");
-            if let Ok(dir_entries) = fs::read_dir(&user_autostart) {
-                for entry in dir_entries.flatten() {
-                    let path = entry.path();
-                    if path.extension().and_then(|s| s.to_str()) == Some("desktop") {
-                        if let Ok(autostart_entry) = Self::from_file(&path) {
-                            seen_names.insert(autostart_entry.name.clone(), true);
-                            entries.push(autostart_entry);
-                        }
+            for path in paths {
+                let id = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                if let Some(overriding) = seen_ids.get(&id) {
+                    if let Some(index) = overriding {
+                        entries[*index].shadows.push(path);
+                    }
+                    continue;
+                }
+
+                match Self::load(&shared_env, &path, source) {
+                    Ok(autostart_entry) => {
+                        seen_ids.insert(id, Some(entries.len()));
+                        entries.push(autostart_entry);
+                    }
+                    Err(error) => {
+                        seen_ids.insert(id, None);
+                        let repairable = source == EntrySource::User
+                            && matches!(
+                                error,
+                                AutostartError::Parse { .. } | AutostartError::MissingKey { key: "Name", .. }
+                            )
+                            && Self::repaired(env, &path).is_ok();
+                        problems.push(LoadProblem {
+                            path,
+                            source,
+                            error,
+                            repairable,
+                        });
                     }
                 }
             }
         }
 
-        // System autostart directories
-        let system_dirs = vec![
-            "/etc/xdg/autostart",
-            "/usr/share/gnome/autostart",
-        ];
-
-        for dir in system_dirs {
-            let autostart_dir = PathBuf::from(dir);
-            if let Ok(dir_entries) = fs::read_dir(&autostart_dir) {
-                for entry in dir_entries.flatten() {
-                    let path = entry.path();
-                    if path.extension().and_then(|s| s.to_str()) == Some("desktop") {
-                        if let Ok(autostart_entry) = Self::from_file(&path) {
-                            // Skip if user already overrode this
-                            if !seen_names.contains_key(&autostart_entry.name) {
-                                entries.push(autostart_entry);
-                            }
-                        }
-                    }
+        // Show hidden files without a name or command like the file they hide
+        for entry in entries
+            .iter_mut()
+            .filter(|entry| entry.hidden && (entry.name.is_empty() || entry.exec.is_empty()))
+        {
+            if let Some(hidden) = entry
+                .shadows
+                .first()
+                .and_then(|path| Self::load(&shared_env, path, EntrySource::System).ok())
+            {
+                if entry.name.is_empty() {
+                    entry.name = hidden.name;
+                }
+                if entry.exec.is_empty() {
+                    entry.exec = hidden.exec;
                 }
             }
+            if entry.name.is_empty() {
+                entry.name = entry
+                    .file_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+            }
         }
 
         entries.sort_by(|a, b| a.name.cmp(&b.name));
-        entries
+        (entries, problems)
     }
 
-    /// Delete this autostart entry
-    pub fn delete(&self) -> Result<(), String> {
-        if !self.is_user_entry {
-            // For system entries, create a user override that hides it
-            if let Some(user_dir) = glib::user_config_dir().as_path().to_str() {
-                let user_autostart = PathBuf::from(user_dir).join("autostart");
-                fs::create_dir_all(&user_autostart)
-                    .map_err(|e| format!("Failed to create autostart directory: {}", e))?;
+    /// Try to make a user file that can't be loaded usable again. Invalid
+    /// UTF-8 is replaced, invalid lines are commented out and a missing name
+    /// is taken from the file name.
+    ///
+    /// The file is left untouched if it can't be repaired, e.g. because it
+    /// has no command.
+    pub fn repair(env: &Environment, path: &Path) -> Result<(), AutostartError> {
+        let desktop_file = Self::repaired(env, path)?;
+        write_atomic(&env.resolve(path), desktop_file.to_string().as_bytes())
+    }
+
+    /// Get the content [`Self::repair`] would write to the file at `path`
+    fn repaired(env: &Environment, path: &Path) -> Result<DesktopFile, AutostartError> {
+        let bytes = fs::read(env.resolve(path)).map_err(|e| AutostartError::io(path, e))?;
+        let mut lines: Vec<String> = String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(str::to_string)
+            .collect();
+
+        // Comment out one invalid line after the other
+        let mut desktop_file = loop {
+            match DesktopFile::parse(&lines.join("\n")) {
+                Ok(desktop_file) => break desktop_file,
+                Err(e) => match lines.get_mut(e.line.saturating_sub(1)) {
+                    Some(line) if !line.trim_start().starts_with('#') => {
+                        *line = format!("# {}", line);
+                    }
+                    _ => {
+                        return Err(AutostartError::Parse {
+                            path: path.to_path_buf(),
+                            line: e.line,
+                            message: e.message,
+                        })
+                    }
+                },
+            }
+        };
+
+        if desktop_file
+            .get_localized(DESKTOP_ENTRY_GROUP, "Name", &current_locales())
+            .is_none()
+        {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            desktop_file.set_string(DESKTOP_ENTRY_GROUP, "Name", &name);
+        }
+
+        // Only repair the file if it can be loaded afterwards
+        Self::from_desktop_file(&Arc::new(env.clone()), desktop_file.clone(), path, EntrySource::User)?;
+        Ok(desktop_file)
+    }
 
-                let filename = self.file_path.file_name()
-                    .ok_or("Invalid file name")?;
-                let user_file = user_autostart.join(filename);
+    /// Get the path of this entry in the user's autostart directory. Changes
+    /// to system entries are written there.
+    fn user_path(&self) -> Result<PathBuf, AutostartError> {
+        let filename = self.file_path.file_name().ok_or_else(|| AutostartError::Io {
+            path: self.file_path.clone(),
+            source: io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"),
+        })?;
 
-                let content = format!(
-                    "[Desktop Entry]\nType=Application\nName={}\nExec={}\nHidden=true\n",
-                    self.name, self.exec
-                );
+        Ok(self.env.user_autostart_dir().join(filename))
+    }
 
-                fs::write(&user_file, content)
-                    .map_err(|e| format!("Failed to write override file: {}", e))?;
+    /// Get the path of this entry in the user's autostart directory, creating
+    /// the directory if needed
+    fn user_file(&self) -> Result<PathBuf, AutostartError> {
+        let user_autostart = self.env.user_autostart_dir();
+
+        fs::create_dir_all(&user_autostart)
+            .map_err(|e| AutostartError::io(&user_autostart, e))?;
+
+        self.user_path()
+    }
+
+    /// Record the file that saving or deleting this entry changes, so the
+    /// change can be undone
+    pub fn snapshot(&self) -> Result<FileSnapshot, AutostartError> {
+        FileSnapshot::take(&self.env.resolve(&self.user_path()?))
+    }
+
+    /// Make sure the file about to be written wasn't changed since this entry
+    /// was loaded, so changes by other programs aren't overwritten blindly
+    fn check_unchanged(&self, target: &Path) -> Result<(), AutostartError> {
+        let unchanged = if target == self.file_path {
+            match &self.stamp {
+                Some(stamp) => stamp.matches(&self.env.resolve(target)),
+                None => !self.env.resolve(target).exists(),
             }
         } else {
-            // For user entries, just delete the file
-            fs::remove_file(&self.file_path)
-                .map_err(|e| format!("Failed to delete file: {}", e))?;
+            // Writing an override for a system entry, which didn't exist when
+            // this entry was loaded since it would have shadowed the system
+            // file. The system file itself may have been updated since, e.g.
+            // by a package upgrade, and the override would hide the update.
+            let system_unchanged = match &self.stamp {
+                Some(stamp) => stamp.matches(&self.env.resolve(&self.file_path)),
+                None => true,
+            };
+            system_unchanged && !self.env.resolve(target).exists()
+        };
+
+        if unchanged {
+            Ok(())
+        } else {
+            Err(AutostartError::Conflict {
+                path: target.to_path_buf(),
+            })
         }
+    }
+
+    /// Write this entry as a new user entry, failing if the file already exists
+    pub fn create(&self) -> Result<(), AutostartError> {
+        if self.env.resolve(&self.file_path).exists() {
+            return Err(AutostartError::NameCollision {
+                path: self.file_path.clone(),
+            });
+        }
+
+        self.validate()?;
+        self.save()?;
         Ok(())
     }
 
-    /// Helper method to write a desktop file with all fields
-    fn write_desktop_file(&self, exec: &str, enabled: bool, path: &Path) -> Result<(), String> {
-        let mut content = String::from("[Desktop Entry]\n");
-        content.push_str("Type=Application\n");
-        content.push_str(&format!("Name={}\n", self.name));
-        content.push_str(&format!("Exec={}\n", exec));
-        content.push_str("Terminal=false\n");
+    /// Delete this autostart entry. User files are moved to the directory of
+    /// removed entries, so they can be restored.
+    pub fn delete(&self) -> Result<(), AutostartError> {
+        self.delete_with(|path| move_to_removed(&self.env, path))
+    }
 
-        if let Some(icon) = &self.icon {
-            content.push_str(&format!("Icon={}\n", icon));
+    /// Delete this autostart entry, getting rid of user files with `remove_file`
+    pub fn delete_with(
+        &self,
+        remove_file: impl FnOnce(&Path) -> Result<(), AutostartError>,
+    ) -> Result<(), AutostartError> {
+        if !self.is_removed_on_delete() {
+            // For system entries, create a user override that hides it
+            let user_file = self.user_file()?;
+            self.check_unchanged(&user_file)?;
+
+            let mut stub = DesktopFile::default();
+            stub.set(DESKTOP_ENTRY_GROUP, "Type", "Application");
+            stub.set_string(DESKTOP_ENTRY_GROUP, "Name", &self.name);
+            stub.set_string(DESKTOP_ENTRY_GROUP, "Exec", &self.exec);
+            stub.set(DESKTOP_ENTRY_GROUP, "Hidden", "true");
+
+            write_atomic(&self.env.resolve(&user_file), stub.to_string().as_bytes())?;
+        } else {
+            remove_file(&self.file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the fields of this entry to its desktop file, only touching the
+    /// keys Boot Mate manages
+    fn to_desktop_file(&self) -> DesktopFile {
+        let mut file = self.desktop_file.clone();
+        let group = DESKTOP_ENTRY_GROUP;
+        let locales = current_locales();
+
+        if file.get(group, "Type").is_none() {
+            file.set(group, "Type", "Application");
+        }
+        file.set_localized(group, "Name", &locales, Some(&self.name));
+        file.set_string(group, "Exec", &self.exec);
+
+        match &self.icon {
+            Some(icon) => file.set_string(group, "Icon", icon),
+            None => file.remove(group, "Icon"),
+        }
+
+        file.set_localized(group, "Comment", &locales, self.comment.as_deref());
+
+        for (key, items) in [("OnlyShowIn", &self.only_show_in), ("NotShowIn", &self.not_show_in)] {
+            if items.is_empty() {
+                file.remove(group, key);
+            } else {
+                file.set_string_list(group, key, items);
+            }
         }
 
-        if let Some(comment) = &self.comment {
-            content.push_str(&format!("Comment={}\n", comment));
+        let currently_enabled = file
+            .get(group, "X-GNOME-Autostart-enabled")
+            .map(|value| value.to_lowercase() != "false")
+            .unwrap_or(true);
+        if currently_enabled != self.enabled {
+            file.set(group, "X-GNOME-Autostart-enabled", if self.enabled { "true" } else { "false" });
         }
 
-        if enabled {
-            content.push_str("X-GNOME-Autostart-enabled=true\n");
+        let current_delay = file
+            .get(group, "X-GNOME-Autostart-Delay")
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .filter(|delay| *delay > 0);
+        if current_delay != self.delay {
+            match self.delay {
+                Some(delay) => file.set(group, "X-GNOME-Autostart-Delay", &delay.to_string()),
+                None => file.remove(group, "X-GNOME-Autostart-Delay"),
+            }
+        }
+
+        let currently_hidden = file
+            .get(group, "Hidden")
+            .map(|value| value.to_lowercase() == "true")
+            .unwrap_or(false);
+        if currently_hidden != self.hidden {
+            if self.hidden {
+                file.set(group, "Hidden", "true");
+            } else {
+                file.remove(group, "Hidden");
+            }
+        }
+
+        file
+    }
+
+    /// Get the content the file of this entry gets when it is saved
+    pub fn to_file_content(&self) -> String {
+        self.to_desktop_file().to_string()
+    }
+
+    /// Save changes to this entry, returning the entry as it is stored now.
+    ///
+    /// Fails with [`AutostartError::Conflict`] if the file was changed by
+    /// another program since the entry was loaded.
+    pub fn save(&self) -> Result<Self, AutostartError> {
+        let user_file = self.user_file()?;
+        self.check_unchanged(&user_file)?;
+        write_atomic(&self.env.resolve(&user_file), self.to_file_content().as_bytes())?;
+        self.reload_saved(&user_file)
+    }
+
+    /// Save changes to this entry, replacing any changes by other programs
+    pub fn save_overwrite(&self) -> Result<Self, AutostartError> {
+        let user_file = self.user_file()?;
+        write_atomic(&self.env.resolve(&user_file), self.to_file_content().as_bytes())?;
+        self.reload_saved(&user_file)
+    }
+
+    /// Read back the user file this entry was just saved to, which now
+    /// overrides the file it was loaded from if that was a system file
+    fn reload_saved(&self, user_file: &Path) -> Result<Self, AutostartError> {
+        let mut saved = Self::load(&self.env, user_file, EntrySource::User)?;
+        saved.shadows = self.shadows.clone();
+        if self.file_path != user_file {
+            saved.shadows.insert(0, self.file_path.clone());
+        }
+        Ok(saved)
+    }
+
+    /// Set the enabled state of this entry, returning the entry as it is stored now
+    pub fn set_enabled(&self, enabled: bool) -> Result<Self, AutostartError> {
+        let mut entry = self.clone();
+        entry.enabled = enabled;
+        entry.save()
+    }
+
+    /// Apply the changes made to this entry on top of its file as it is on
+    /// disk now, keeping the changes another program made to other keys.
+    ///
+    /// The returned entry can be saved without a conflict.
+    pub fn merge_with_disk(&self) -> Result<Self, AutostartError> {
+        let user_file = self.user_file()?;
+        let mut merged = if self.env.resolve(&user_file).exists() {
+            Self::load(&self.env, &user_file, EntrySource::User)?
         } else {
-            content.push_str("X-GNOME-Autostart-enabled=false\n");
+            Self::load(&self.env, &self.file_path, self.source)?
+        };
+        merged.shadows = self.shadows.clone();
+
+        // The entry as it was loaded, to find out what was changed
+        let base = Self::from_desktop_file(
+            &self.env,
+            self.desktop_file.clone(),
+            &self.file_path,
+            self.source,
+        )?;
+
+        if self.name != base.name {
+            merged.name = self.name.clone();
+        }
+        if self.exec != base.exec {
+            merged.exec = self.exec.clone();
+        }
+        if self.icon != base.icon {
+            merged.icon = self.icon.clone();
+        }
+        if self.comment != base.comment {
+            merged.comment = self.comment.clone();
+        }
+        if self.only_show_in != base.only_show_in {
+            merged.only_show_in = self.only_show_in.clone();
+        }
+        if self.not_show_in != base.not_show_in {
+            merged.not_show_in = self.not_show_in.clone();
+        }
+        if self.enabled != base.enabled {
+            merged.enabled = self.enabled;
+        }
+        if self.delay != base.delay {
+            merged.delay = self.delay;
+        }
+        if self.hidden != base.hidden {
+            merged.hidden = self.hidden;
         }
 
-        fs::write(path, content)
-            .map_err(|e| format!("Failed to write file: {}", e))?;
+        Ok(merged)
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "# Shipped by the vendor
+[Desktop Entry]
+Type=Application
+Name=Files
+Name[de]=Dateien
+Comment=Browse files
+Exec=nautilus --new-window
+X-Vendor-Key = kept as is
+Actions=new-window;
+
+[Desktop Action new-window]
+Name=New Window
+Exec=nautilus --new-window
+";
+
+    #[test]
+    fn unchanged_file_is_written_back_as_is() {
+        let file = DesktopFile::parse(FILE).unwrap();
+        assert_eq!(file.to_string(), FILE);
     }
 
-    /// Save changes to this entry
-    pub fn save(&self, new_exec: &str) -> Result<(), String> {
-        let user_dir = glib::user_config_dir();
-        let user_autostart = user_dir.join("autostart");
+    #[test]
+    fn only_changed_lines_are_rewritten() {
+        let mut file = DesktopFile::parse(FILE).unwrap();
+        file.set(DESKTOP_ENTRY_GROUP, "Exec", "nautilus");
+        file.set(DESKTOP_ENTRY_GROUP, "X-GNOME-Autostart-enabled", "false");
 
-        fs::create_dir_all(&user_autostart)
-            .map_err(|e| format!("Failed to create autostart directory: {}", e))?;
+        assert_eq!(
+            file.to_string(),
+            FILE.replacen("Exec=nautilus --new-window\n", "Exec=nautilus\n", 1)
+                .replace("Actions=new-window;\n", "Actions=new-window;\nX-GNOME-Autostart-enabled=false\n")
+        );
+        assert_eq!(file.get("Desktop Action new-window", "Exec"), Some("nautilus --new-window"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "X-Vendor-Key"), Some("kept as is"));
 
-        let filename = self.file_path.file_name()
-            .ok_or("Invalid file name")?;
-        let user_file = user_autostart.join(filename);
+        file.remove(DESKTOP_ENTRY_GROUP, "X-GNOME-Autostart-enabled");
+        file.set(DESKTOP_ENTRY_GROUP, "Exec", "nautilus --new-window");
+        assert_eq!(file.to_string(), FILE);
+    }
 
-        self.write_desktop_file(new_exec, self.enabled, &user_file)
+    #[test]
+    fn invalid_lines_are_reported() {
+        let error = DesktopFile::parse("[Desktop Entry]\nName=App\nno separator\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(DesktopFile::parse("Name=App\n").is_err());
+        assert!(DesktopFile::parse("[Desktop Entry\n").is_err());
+        assert!(DesktopFile::parse("[Desktop Entry]\n=value\n").is_err());
     }
 
-    /// Set the enabled state of this entry
-    pub fn set_enabled(&self, enabled: bool) -> Result<(), String> {
-        let user_dir = glib::user_config_dir();
-        let user_autostart = user_dir.join("autostart");
+    #[test]
+    fn locale_variants_follow_the_spec_order() {
+        assert_eq!(
+            locale_variants("sr_RS.UTF-8@latin"),
+            vec!["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+        assert_eq!(locale_variants("de_CH.UTF-8"), vec!["de_CH", "de"]);
+        assert_eq!(locale_variants("fr"), vec!["fr"]);
+        assert!(locale_variants("C.UTF-8").is_empty());
+        assert!(locale_variants("POSIX").is_empty());
+        assert!(locale_variants("").is_empty());
+    }
 
-        fs::create_dir_all(&user_autostart)
-            .map_err(|e| format!("Failed to create autostart directory: {}", e))?;
+    #[test]
+    fn localized_values_are_looked_up_and_written() {
+        let mut file = DesktopFile::parse(FILE).unwrap();
+        let swiss = locale_variants("de_CH");
+        let french = locale_variants("fr_FR");
+
+        assert_eq!(
+            file.get_localized(DESKTOP_ENTRY_GROUP, "Name", &swiss),
+            Some(("Name[de]".to_string(), "Dateien".to_string()))
+        );
+        assert_eq!(
+            file.get_localized(DESKTOP_ENTRY_GROUP, "Name", &french),
+            Some(("Name".to_string(), "Files".to_string()))
+        );
+
+        // The translation that is shown is the one changed
+        file.set_localized(DESKTOP_ENTRY_GROUP, "Name", &swiss, Some("Ordner"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "Name[de]"), Some("Ordner"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "Name"), Some("Files"));
+
+        // A missing translation is added for the language without country
+        file.set_localized(DESKTOP_ENTRY_GROUP, "Name", &french, Some("Fichiers"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "Name[fr]"), Some("Fichiers"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "Name"), Some("Files"));
+
+        // Untranslated keys stay untranslated
+        file.set_localized(DESKTOP_ENTRY_GROUP, "Comment", &swiss, Some("Dateien durchsuchen"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "Comment"), Some("Dateien durchsuchen"));
+        assert_eq!(file.get(DESKTOP_ENTRY_GROUP, "Comment[de]"), None);
+    }
+
+    #[test]
+    fn values_are_unescaped() {
+        assert_eq!(unescape_value(r"Line\none"), "Line\none");
+        assert_eq!(unescape_value(r"\sleading\ttab\rreturn"), " leading\ttab\rreturn");
+        assert_eq!(unescape_value(r#"sh -c "echo \\$HOME""#), r#"sh -c "echo \$HOME""#);
+        // Unknown and incomplete escapes are kept
+        assert_eq!(unescape_value(r"C:\path\"), r"C:\path\");
+    }
+
+    #[test]
+    fn values_are_escaped() {
+        assert_eq!(escape_value("Line\none"), r"Line\none");
+        assert_eq!(escape_value(" a b\t"), r"\sa b\t");
+        assert_eq!(escape_value(r"back\slash"), r"back\\slash");
+
+        for value in ["Line\none", " leading", "tab\tand\rreturn", r"echo \$HOME", "plain"] {
+            assert_eq!(unescape_value(&escape_value(value)), value);
+        }
+    }
+
+    #[test]
+    fn escaped_values_are_written_to_the_file() {
+        let mut file = DesktopFile::parse("[Desktop Entry]\nName=App\n").unwrap();
+        file.set_string(DESKTOP_ENTRY_GROUP, "Comment", "Two\nlines");
+        assert_eq!(file.to_string(), "[Desktop Entry]\nName=App\nComment=Two\\nlines\n");
+        assert_eq!(file.get_string(DESKTOP_ENTRY_GROUP, "Comment").as_deref(), Some("Two\nlines"));
+    }
+
+    #[test]
+    fn string_lists_are_split_and_joined() {
+        assert_eq!(parse_string_list("GNOME;KDE;"), vec!["GNOME", "KDE"]);
+        assert_eq!(parse_string_list("GNOME;KDE"), vec!["GNOME", "KDE"]);
+        assert_eq!(parse_string_list(r"a\;b;c\sd;"), vec!["a;b", "c d"]);
+        assert_eq!(parse_string_list(r"ends\;here;"), vec!["ends;here"]);
+        assert_eq!(parse_string_list(r"ends\\;next;"), vec![r"ends\", "next"]);
+        assert!(parse_string_list("").is_empty());
+
+        let items = vec!["a;b".to_string(), " c".to_string(), r"d\".to_string()];
+        assert_eq!(format_string_list(&items), r"a\;b;\sc;d\\;");
+        assert_eq!(parse_string_list(&format_string_list(&items)), items);
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn exec_is_split_into_arguments() {
+        assert_eq!(parse_exec("firefox  --new-window %u").unwrap(), args(&["firefox", "--new-window", "%u"]));
+        assert_eq!(
+            parse_exec(r#"sh -c "echo \$HOME \"quoted\" \\ \`date\`""#).unwrap(),
+            args(&["sh", "-c", r#"echo $HOME "quoted" \ `date`"#])
+        );
+        assert_eq!(parse_exec(r#"app "" "a b"c"#).unwrap(), args(&["app", "", "a bc"]));
+    }
+
+    #[test]
+    fn malformed_exec_is_rejected() {
+        for exec in [
+            r#"app "unterminated"#,
+            r#"app "bad \n escape""#,
+            r#"app "ends with \"#,
+            "",
+            "   ",
+            "sh -c 'echo hi' %U",
+            "app $HOME",
+            "app > log",
+            r#"app \"quoted\""#,
+        ] {
+            assert!(
+                matches!(parse_exec(exec), Err(AutostartError::InvalidExec { .. })),
+                "{:?} was accepted",
+                exec
+            );
+        }
+    }
+
+    #[test]
+    fn field_codes_are_expanded_for_autostart() {
+        let path = Path::new("/etc/xdg/autostart/app.desktop");
+        let expand = |exec: &str, icon: Option<&str>| {
+            expand_field_codes(&parse_exec(exec).unwrap(), "My App", icon, path)
+        };
+
+        assert_eq!(expand("app %U --flag", None).unwrap(), args(&["app", "--flag"]));
+        assert_eq!(expand("app %f %F %u %d %D %n %N %v %m", None).unwrap(), args(&["app"]));
+        assert_eq!(expand("app --file=%f 100%%", None).unwrap(), args(&["app", "--file=", "100%"]));
+        assert_eq!(expand("app %i", Some("app-icon")).unwrap(), args(&["app", "--icon", "app-icon"]));
+        assert_eq!(expand("app %i", None).unwrap(), args(&["app"]));
+        assert_eq!(
+            expand("app --name=%c %k", None).unwrap(),
+            args(&["app", "--name=My App", "/etc/xdg/autostart/app.desktop"])
+        );
+
+        assert!(matches!(expand("app %x", None), Err(AutostartError::InvalidExec { .. })));
+        assert!(matches!(expand("app 100%", None), Err(AutostartError::InvalidExec { .. })));
+    }
+
+    #[test]
+    fn exec_is_quoted_where_needed() {
+        let arguments = args(&["app", "a b", "100%", r#"say "hi" $USER"#, ""]);
+        let exec = format_exec(&arguments);
+        assert_eq!(exec, r#"app "a b" 100%% "say \"hi\" \$USER" """#);
+        assert_eq!(
+            expand_field_codes(&parse_exec(&exec).unwrap(), "App", None, Path::new("app.desktop")).unwrap(),
+            arguments
+        );
+    }
+
+    #[test]
+    fn first_listed_desktop_decides_where_entry_is_shown() {
+        let mut entry = AutostartEntry::new(&Environment::from_env(), "App", "app");
+        assert!(entry.shown_in(&args(&["GNOME"])));
+        assert!(entry.shown_in(&[]));
+
+        entry.only_show_in = args(&["GNOME", "XFCE"]);
+        assert!(entry.shown_in(&args(&["gnome"])));
+        assert!(entry.shown_in(&args(&["ubuntu", "GNOME"])));
+        assert!(!entry.shown_in(&args(&["KDE"])));
+        assert!(!entry.shown_in(&[]));
+
+        entry.only_show_in = args(&["ubuntu"]);
+        entry.not_show_in = args(&["GNOME"]);
+        assert!(entry.shown_in(&args(&["ubuntu", "GNOME"])));
+        assert!(!entry.shown_in(&args(&["GNOME", "ubuntu"])));
+
+        entry.only_show_in.clear();
+        assert!(!entry.shown_in(&args(&["GNOME"])));
+        assert!(entry.shown_in(&args(&["KDE"])));
+    }
 
-        let filename = self.file_path.file_name()
-            .ok_or("Invalid file name")?;
-        let user_file = user_autostart.join(filename);
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let content = "[Desktop Entry]\r\nName=App\r\nExec=app\r\n";
+        let mut file = DesktopFile::parse(content).unwrap();
+        assert_eq!(file.to_string(), content);
 
-        self.write_desktop_file(&self.exec, enabled, &user_file)
+        file.set(DESKTOP_ENTRY_GROUP, "Hidden", "true");
+        assert_eq!(file.to_string(), "[Desktop Entry]\r\nName=App\r\nExec=app\r\nHidden=true\r\n");
     }
 }
@@ -0,0 +1,344 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+use bootmate::autostart::{AutostartEntry, AutostartError, EntrySource};
+use bootmate::environment::Environment;
+use bootmate::json;
+use gettextrs::gettext;
+use std::path::PathBuf;
+
+/// An operation on the autostart files failed
+pub const EXIT_FAILURE: u8 = 1;
+/// The command line couldn't be parsed
+pub const EXIT_USAGE: u8 = 2;
+/// No entry has the given desktop file ID
+pub const EXIT_NOT_FOUND: u8 = 3;
+/// An entry with the same file name already exists
+pub const EXIT_EXISTS: u8 = 4;
+
+const COMMANDS: &[&str] = &["list", "enable", "disable", "add", "remove", "help"];
+
+/// Options before the command, which apply to the graphical interface too
+const ROOT_OPTIONS: &[&str] = &["--root", "--user"];
+
+/// Why a command failed, determining the exit code
+enum CliError {
+    Usage(String),
+    NotFound(String),
+    Failed(String),
+    Autostart(AutostartError),
+}
+
+impl From<AutostartError> for CliError {
+    fn from(error: AutostartError) -> Self {
+        CliError::Autostart(error)
+    }
+}
+
+/// Whether `arg` is a command of the command-line interface rather than an
+/// option for the graphical interface
+pub fn is_command(arg: &str) -> bool {
+    COMMANDS.contains(&arg)
+}
+
+/// Parse the `--root` and `--user` options at the start of `args`, the
+/// command line arguments without the program name. With them, the entries
+/// of a user of the system installed in another root directory are used.
+///
+/// Returns the environment of that user, if the options were given, and the
+/// remaining arguments. If the options are invalid, an error is printed and
+/// the exit code is returned.
+pub fn parse_root_options(args: &[String]) -> Result<(Option<Environment>, &[String]), u8> {
+    parse_root(args).map_err(report)
+}
+
+fn parse_root(mut args: &[String]) -> Result<(Option<Environment>, &[String]), CliError> {
+    let mut root = None;
+    let mut user = None;
+
+    while let Some((arg, rest)) = args
+        .split_first()
+        .filter(|(arg, _)| ROOT_OPTIONS.contains(&option_name(arg)))
+    {
+        let (option, value, rest) = take_option(arg, rest)?;
+        match option {
+            "--root" => root = Some(PathBuf::from(value)),
+            _ => user = Some(value),
+        }
+        args = rest;
+    }
+
+    let env = match (root, user) {
+        (None, None) => None,
+        (None, Some(_)) => return Err(CliError::Usage(gettext("--user can only be used with --root"))),
+        (Some(_), None) => return Err(CliError::Usage(gettext("--root needs --user"))),
+        (Some(root), Some(user)) => {
+            if !root.is_dir() {
+                return Err(CliError::Failed(
+                    gettext("“%s” is not a directory").replace("%s", &root.display().to_string()),
+                ));
+            }
+
+            let home = Environment::user_home_in_root(&root, &user).ok_or_else(|| {
+                CliError::Failed(
+                    gettext("No user “%s” in %s")
+                        .replacen("%s", &user, 1)
+                        .replacen("%s", &root.join("etc/passwd").display().to_string(), 1),
+                )
+            })?;
+
+            Some(Environment::for_root(&root, &home))
+        }
+    };
+
+    Ok((env, args))
+}
+
+/// Get the option of an argument like `--option=value`
+fn option_name(arg: &str) -> &str {
+    arg.split_once('=').map_or(arg, |(option, _)| option)
+}
+
+/// Get the value of the option `arg`, given as `--option=value` or as
+/// `--option value` with the value at the start of `rest`.
+///
+/// Returns the option, its value and the remaining arguments.
+fn take_option<'a>(
+    arg: &'a str,
+    rest: &'a [String],
+) -> Result<(&'a str, String, &'a [String]), CliError> {
+    match arg.split_once('=') {
+        Some((option, value)) => Ok((option, value.to_string(), rest)),
+        None => match rest.split_first() {
+            Some((value, rest)) => Ok((arg, value.clone(), rest)),
+            None => Err(CliError::Usage(
+                gettext("Missing value for “%s”").replace("%s", arg),
+            )),
+        },
+    }
+}
+
+/// Run a command on the autostart entries of `env` without starting the
+/// graphical interface. `args` are the command line arguments without the
+/// program name and the options parsed by [`parse_root_options`].
+///
+/// Returns the exit code of the process.
+pub fn run(env: &Environment, args: &[String]) -> u8 {
+    let result = match args.first().map(String::as_str) {
+        Some("list") => list(env, &args[1..]),
+        Some("enable") => set_enabled(env, &args[1..], true),
+        Some("disable") => set_enabled(env, &args[1..], false),
+        Some("add") => add(env, &args[1..]),
+        Some("remove") => remove(env, &args[1..]),
+        Some("help") => {
+            print!("{}", usage());
+            Ok(())
+        }
+        _ => Err(CliError::Usage(gettext("Unknown command"))),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(error) => report(error),
+    }
+}
+
+/// Print an error, returning the exit code for it
+fn report(error: CliError) -> u8 {
+    match error {
+        CliError::Usage(message) => {
+            eprintln!("bootmate: {}\n\n{}", message, usage());
+            EXIT_USAGE
+        }
+        CliError::NotFound(id) => {
+            eprintln!("bootmate: {}", gettext("No autostart entry “%s”").replace("%s", &id));
+            EXIT_NOT_FOUND
+        }
+        CliError::Failed(message) => {
+            eprintln!("bootmate: {}", message);
+            EXIT_FAILURE
+        }
+        CliError::Autostart(error) => {
+            eprintln!("bootmate: {}", error);
+            match error {
+                AutostartError::NameCollision { .. } => EXIT_EXISTS,
+                _ => EXIT_FAILURE,
+            }
+        }
+    }
+}
+
+/// Get the help text listing all commands
+pub fn usage() -> String {
+    gettext(
+        "Usage:
+  bootmate                             Open the main window
+  bootmate list [--json]               List all autostart entries
+  bootmate enable ID                   Start the entry at login
+  bootmate disable ID                  Don't start the entry at login
+  bootmate add --name NAME --exec CMD  Add a user entry
+  bootmate remove ID                   Remove the entry
+  bootmate help                        Show this help
+
+ID is the file name of the entry, e.g. org.gnome.Software.desktop.
+The .desktop suffix may be left out.
+
+Options, given before the command:
+  --root DIR --user NAME               Use the entries of the user NAME of the
+                                       system installed in DIR, e.g. a mounted
+                                       image, without changing this system
+",
+    )
+}
+
+/// Get the single argument of a command
+fn single_arg(args: &[String]) -> Result<&str, CliError> {
+    match args {
+        [arg] if !arg.starts_with("--") => Ok(arg),
+        [] => Err(CliError::Usage(gettext("Missing entry ID"))),
+        _ => Err(CliError::Usage(gettext("Too many arguments"))),
+    }
+}
+
+/// Find the entry with the given desktop file ID, with or without `.desktop`
+fn find_entry(env: &Environment, id: &str) -> Result<AutostartEntry, CliError> {
+    AutostartEntry::load_all(env)
+        .into_iter()
+        .find(|entry| {
+            let desktop_id = entry.desktop_id();
+            desktop_id == id || desktop_id.strip_suffix(".desktop") == Some(id)
+        })
+        .ok_or_else(|| CliError::NotFound(id.to_string()))
+}
+
+/// Print one line per entry: desktop file ID, state, source and name.
+/// With `--json`, all details are printed as JSON instead.
+fn list(env: &Environment, args: &[String]) -> Result<(), CliError> {
+    let as_json = match args {
+        [] => false,
+        [arg] if arg == "--json" => true,
+        [arg] => {
+            return Err(CliError::Usage(
+                gettext("Unknown option “%s”").replace("%s", arg),
+            ))
+        }
+        _ => return Err(CliError::Usage(gettext("Too many arguments"))),
+    };
+
+    let (entries, problems) = AutostartEntry::load_all_with_problems(env);
+    if as_json {
+        print!("{}", json::to_json(&entries, &problems));
+        return Ok(());
+    }
+
+    let rows: Vec<(String, &str, &str, &str)> = entries
+        .iter()
+        .map(|entry| {
+            let state = if entry.hidden {
+                "hidden"
+            } else if entry.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            let source = if entry.is_override() {
+                "override"
+            } else if entry.source == EntrySource::User {
+                "user"
+            } else {
+                "system"
+            };
+            (entry.desktop_id(), state, source, entry.name.as_str())
+        })
+        .collect();
+
+    let id_width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(0);
+    for (id, state, source, name) in &rows {
+        println!("{:id_width$}  {:8}  {:8}  {}", id, state, source, name);
+    }
+
+    for problem in &problems {
+        eprintln!("bootmate: {}", problem.error);
+    }
+
+    Ok(())
+}
+
+fn set_enabled(env: &Environment, args: &[String], enabled: bool) -> Result<(), CliError> {
+    let entry = find_entry(env, single_arg(args)?)?;
+
+    if entry.hidden {
+        return Err(CliError::Failed(
+            gettext("“%s” is hidden and can only be restored in the main window")
+                .replace("%s", &entry.name),
+        ));
+    }
+
+    if entry.enabled != enabled {
+        entry.set_enabled(enabled)?;
+    }
+
+    let message = if enabled {
+        gettext("“%s” enabled")
+    } else {
+        gettext("“%s” disabled")
+    };
+    println!("{}", message.replace("%s", &entry.name));
+    Ok(())
+}
+
+fn add(env: &Environment, args: &[String]) -> Result<(), CliError> {
+    let mut name = None;
+    let mut exec = None;
+
+    let mut args = args;
+    while let Some((arg, rest)) = args.split_first() {
+        match option_name(arg) {
+            "--name" | "--exec" => {}
+            option => {
+                return Err(CliError::Usage(
+                    gettext("Unknown option “%s”").replace("%s", option),
+                ))
+            }
+        }
+
+        let (option, value, rest) = take_option(arg, rest)?;
+        if option == "--name" {
+            name = Some(value);
+        } else {
+            exec = Some(value);
+        }
+        args = rest;
+    }
+
+    let name = name
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| CliError::Usage(gettext("The name must not be empty")))?;
+    let exec = exec
+        .filter(|exec| !exec.is_empty())
+        .ok_or_else(|| CliError::Usage(gettext("The command must not be empty")))?;
+
+    let entry = AutostartEntry::new(env, &name, &exec);
+    // The file name is made of the letters and digits of the name
+    if entry.desktop_id() == ".desktop" {
+        return Err(CliError::Usage(gettext("The name must contain a letter or digit")));
+    }
+    entry.create()?;
+
+    println!("{}", entry.desktop_id());
+    Ok(())
+}
+
+fn remove(env: &Environment, args: &[String]) -> Result<(), CliError> {
+    let entry = find_entry(env, single_arg(args)?)?;
+
+    if entry.hidden {
+        return Err(CliError::Failed(
+            gettext("“%s” is already hidden").replace("%s", &entry.name),
+        ));
+    }
+
+    entry.delete()?;
+
+    println!("{}", gettext("“%s” deleted").replace("%s", &entry.name));
+    Ok(())
+}
@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
+#[cfg(feature = "gui")]
 pub const APP_ID: &str = "ch.srueegger.bootmate";
+#[cfg(feature = "gui")]
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const GETTEXT_PACKAGE: &str = "bootmate";
 pub const LOCALEDIR: &str = "/usr/share/locale";
-pub const PKGDATADIR: &str = "/usr/share/bootmate";
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+use bootmate::autostart::AutostartEntry;
+use glib::subclass::Signal;
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub struct EntryObject {
+        pub entry: RefCell<Option<AutostartEntry>>,
+        /// The program of the entry that is not installed, looked up off the
+        /// main thread when the entry was loaded
+        pub missing_program: RefCell<Option<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for EntryObject {
+        const NAME: &'static str = "BootMateEntryObject";
+        type Type = super::EntryObject;
+    }
+
+    impl ObjectImpl for EntryObject {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("changed").build()])
+        }
+    }
+}
+
+glib::wrapper! {
+    /// An autostart entry as item of a list model
+    pub struct EntryObject(ObjectSubclass<imp::EntryObject>);
+}
+
+impl EntryObject {
+    pub fn new(entry: AutostartEntry, missing_program: Option<String>) -> Self {
+        let obj: Self = glib::Object::new();
+        obj.imp().entry.replace(Some(entry));
+        obj.imp().missing_program.replace(missing_program);
+        obj
+    }
+
+    pub fn entry(&self) -> AutostartEntry {
+        self.imp()
+            .entry
+            .borrow()
+            .clone()
+            .expect("EntryObject without entry")
+    }
+
+    /// Desktop file ID of the entry, which identifies it across reloads
+    pub fn desktop_id(&self) -> String {
+        self.imp()
+            .entry
+            .borrow()
+            .as_ref()
+            .map(AutostartEntry::desktop_id)
+            .unwrap_or_default()
+    }
+
+    /// The program of the entry that is not installed, see
+    /// [`AutostartEntry::missing_program`]
+    pub fn missing_program(&self) -> Option<String> {
+        self.imp().missing_program.borrow().clone()
+    }
+
+    /// Replace the entry, notifying rows showing it if anything changed
+    pub fn set_entry(&self, entry: AutostartEntry, missing_program: Option<String>) {
+        if self.imp().entry.borrow().as_ref() == Some(&entry)
+            && *self.imp().missing_program.borrow() == missing_program
+        {
+            return;
+        }
+
+        self.imp().entry.replace(Some(entry));
+        self.imp().missing_program.replace(missing_program);
+        self.emit_by_name::<()>("changed", &[]);
+    }
+
+    pub fn connect_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("changed", false, move |values| {
+            let obj = values[0]
+                .get::<Self>()
+                .expect("changed signal without EntryObject");
+            f(&obj);
+            None
+        })
+    }
+}
@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use crate::autostart::AutostartEntry;
+use bootmate::autostart::{current_desktops, AutostartEntry, AutostartError, FileSnapshot};
+use crate::entry_object::EntryObject;
 use libadwaita as adw;
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gettextrs::gettext;
-use gtk::glib;
+use gtk::{gio, glib};
 use std::cell::RefCell;
 
 mod imp {
@@ -13,7 +14,17 @@ mod imp {
 
     #[derive(Debug, Default)]
     pub struct EntryRow {
-        pub entry: RefCell<Option<AutostartEntry>>,
+        pub entry_object: RefCell<Option<EntryObject>>,
+        pub enable_switch: gtk::Switch,
+        pub icon: gtk::Image,
+        pub desktop_indicator: gtk::Image,
+        pub delay_label: gtk::Label,
+        pub missing_label: gtk::Label,
+        pub overridden_label: gtk::Label,
+        pub reset_button: gtk::Button,
+        pub unhide_button: gtk::Button,
+        pub edit_button: gtk::Button,
+        pub delete_button: gtk::Button,
     }
 
     #[glib::object_subclass]
@@ -37,143 +48,325 @@ glib::wrapper! {
 }
 
 impl EntryRow {
-    pub fn new(entry: &AutostartEntry) -> Self {
+    /// Create a row showing `entry_object`, which is kept up to date when the
+    /// entry changes
+    pub fn new(entry_object: &EntryObject) -> Self {
         let row: Self = glib::Object::new();
-
-        // Escape markup characters in title and subtitle to prevent parsing errors
-        row.set_title(&glib::markup_escape_text(&entry.name));
-
-        // Set subtitle (exec command)
-        row.set_subtitle(&glib::markup_escape_text(&entry.exec));
+        let imp = row.imp();
 
         // Create a horizontal box for prefix (switch + icon)
         let prefix_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
 
         // Add enable/disable switch
-        let enable_switch = gtk::Switch::builder()
-            .active(entry.enabled)
-            .valign(gtk::Align::Center)
-            .tooltip_text(if entry.enabled {
-                gettext("Enabled")
-            } else {
-                gettext("Disabled")
-            })
-            .build();
-
-        prefix_box.append(&enable_switch);
+        imp.enable_switch.set_valign(gtk::Align::Center);
+        prefix_box.append(&imp.enable_switch);
 
         // Set icon with larger size
-        let icon = if let Some(icon_name) = &entry.icon {
-            gtk::Image::builder()
-                .icon_name(icon_name)
-                .pixel_size(32)
-                .build()
-        } else {
-            gtk::Image::builder()
-                .icon_name("application-x-executable")
-                .pixel_size(32)
-                .build()
-        };
-
-        prefix_box.append(&icon);
+        imp.icon.set_pixel_size(32);
+        prefix_box.append(&imp.icon);
         row.add_prefix(&prefix_box);
 
-        // Apply visual styling for disabled entries
-        if !entry.enabled {
-            row.set_opacity(0.5);
-            row.add_css_class("dim-label");
-        }
+        // Indicate that the entry is not started in the current desktop
+        imp.desktop_indicator.set_icon_name(Some("action-unavailable-symbolic"));
+        imp.desktop_indicator.set_valign(gtk::Align::Center);
+        imp.desktop_indicator.set_tooltip_text(Some(&gettext("Not started in this desktop environment")));
+        row.add_suffix(&imp.desktop_indicator);
+
+        // Show the start delay
+        imp.delay_label.set_valign(gtk::Align::Center);
+        imp.delay_label.add_css_class("dim-label");
+        imp.delay_label.add_css_class("caption");
+        row.add_suffix(&imp.delay_label);
+
+        // Mark entries whose program has been uninstalled
+        imp.missing_label.set_label(&gettext("Program not found"));
+        imp.missing_label.set_valign(gtk::Align::Center);
+        imp.missing_label.add_css_class("error");
+        imp.missing_label.add_css_class("caption");
+        row.add_suffix(&imp.missing_label);
+
+        // Mark system entries the user changed
+        imp.overridden_label.set_label(&gettext("Overridden"));
+        imp.overridden_label.set_valign(gtk::Align::Center);
+        imp.overridden_label
+            .set_tooltip_text(Some(&gettext("Your changes override the system entry")));
+        imp.overridden_label.add_css_class("accent");
+        imp.overridden_label.add_css_class("caption");
+        row.add_suffix(&imp.overridden_label);
+
+        // Add Reset button for overridden system entries
+        imp.reset_button.set_icon_name("edit-undo-symbolic");
+        imp.reset_button.set_valign(gtk::Align::Center);
+        imp.reset_button.set_tooltip_text(Some(&gettext("Reset to System Default")));
+        imp.reset_button.add_css_class("flat");
+
+        // Add Restore button for hidden entries
+        imp.unhide_button.set_label(&gettext("Restore"));
+        imp.unhide_button.set_valign(gtk::Align::Center);
 
         // Add Edit button
-        let edit_button = gtk::Button::builder()
-            .icon_name("document-edit-symbolic")
-            .valign(gtk::Align::Center)
-            .tooltip_text(gettext("Edit"))
-            .build();
-        edit_button.add_css_class("flat");
+        imp.edit_button.set_icon_name("document-edit-symbolic");
+        imp.edit_button.set_valign(gtk::Align::Center);
+        imp.edit_button.set_tooltip_text(Some(&gettext("Edit")));
+        imp.edit_button.add_css_class("flat");
 
         // Add Delete button
-        let delete_button = gtk::Button::builder()
-            .icon_name("user-trash-symbolic")
-            .valign(gtk::Align::Center)
-            .tooltip_text(gettext("Delete"))
-            .build();
-        delete_button.add_css_class("flat");
-        delete_button.add_css_class("destructive-action");
+        imp.delete_button.set_icon_name("user-trash-symbolic");
+        imp.delete_button.set_valign(gtk::Align::Center);
+        imp.delete_button.set_tooltip_text(Some(&gettext("Delete")));
+        imp.delete_button.add_css_class("flat");
+        imp.delete_button.add_css_class("destructive-action");
 
         let button_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
-        button_box.append(&edit_button);
-        button_box.append(&delete_button);
+        button_box.append(&imp.unhide_button);
+        button_box.append(&imp.reset_button);
+        button_box.append(&imp.edit_button);
+        button_box.append(&imp.delete_button);
 
         row.add_suffix(&button_box);
 
-        // Store entry data
-        row.imp().entry.replace(Some(entry.clone()));
+        // Store entry data and follow its changes
+        imp.entry_object.replace(Some(entry_object.clone()));
+        entry_object.connect_changed(glib::clone!(
+            #[weak] row,
+            move |_| row.update()
+        ));
+        row.update();
 
         // Connect switch toggle handler
-        let entry_clone = entry.clone();
-        enable_switch.connect_state_set(glib::clone!(
+        imp.enable_switch.connect_state_set(glib::clone!(
             #[weak] row,
             #[upgrade_or] glib::Propagation::Proceed,
             move |switch, enabled| {
-                // Update visual state immediately
-                if enabled {
-                    row.set_opacity(1.0);
-                    row.remove_css_class("dim-label");
-                    switch.set_tooltip_text(Some(&gettext("Enabled")));
-                } else {
-                    row.set_opacity(0.5);
-                    row.add_css_class("dim-label");
-                    switch.set_tooltip_text(Some(&gettext("Disabled")));
+                let Some(entry) = row.entry() else {
+                    return glib::Propagation::Proceed;
+                };
+
+                // Nothing to save when the switch follows a change of the entry
+                if entry.enabled == enabled {
+                    return glib::Propagation::Proceed;
                 }
 
                 // Save the change
-                let mut modified_entry = entry_clone.clone();
+                let mut modified_entry = entry.clone();
                 modified_entry.enabled = enabled;
-
-                if let Err(e) = modified_entry.set_enabled(enabled) {
-                    eprintln!("Failed to set enabled state: {}", e);
-                    // Revert the switch state on error
-                    switch.set_active(!enabled);
-                    row.set_opacity(if !enabled { 1.0 } else { 0.5 });
-                    if !enabled {
-                        row.remove_css_class("dim-label");
-                    } else {
-                        row.add_css_class("dim-label");
+                let snapshot = entry.snapshot().ok();
+
+                match modified_entry.set_enabled(enabled) {
+                    Ok(saved_entry) => {
+                        row.set_entry(saved_entry);
+                        let title = if enabled {
+                            gettext("“%s” enabled")
+                        } else {
+                            gettext("“%s” disabled")
+                        };
+                        row.report_change(&title.replace("%s", &entry.name), snapshot);
+                    }
+                    Err(e) => {
+                        row.report_save_error(&e, &modified_entry);
+                        // Revert the switch state on error
+                        switch.set_active(!enabled);
+                        return glib::Propagation::Stop;
                     }
-                    return glib::Propagation::Stop;
-                }
-
-                // Refresh the entire list to ensure consistency
-                if let Some(window) = row.root().and_downcast::<crate::window::BootMateWindow>() {
-                    window.load_autostart_entries();
                 }
 
                 glib::Propagation::Proceed
             }
         ));
 
+        // Connect restore button
+        imp.unhide_button.connect_clicked(glib::clone!(
+            #[weak] row,
+            move |_| {
+                if let Some(entry) = row.entry() {
+                    row.unhide(&entry);
+                }
+            }
+        ));
+
         // Connect edit button
-        let entry_clone = entry.clone();
-        edit_button.connect_clicked(glib::clone!(
+        imp.edit_button.connect_clicked(glib::clone!(
+            #[weak] row,
+            move |_| {
+                if let Some(entry) = row.entry() {
+                    row.show_edit_dialog(&entry);
+                }
+            }
+        ));
+
+        // Connect reset button
+        imp.reset_button.connect_clicked(glib::clone!(
             #[weak] row,
             move |_| {
-                row.show_edit_dialog(&entry_clone);
+                if let Some(entry) = row.entry() {
+                    row.show_reset_dialog(&entry);
+                }
             }
         ));
 
         // Connect delete button
-        let entry_clone = entry.clone();
-        delete_button.connect_clicked(glib::clone!(
+        imp.delete_button.connect_clicked(glib::clone!(
             #[weak] row,
             move |_| {
-                row.show_delete_dialog(&entry_clone);
+                if let Some(entry) = row.entry() {
+                    row.show_delete_dialog(&entry);
+                }
             }
         ));
 
         row
     }
 
+    /// The entry currently shown by this row
+    fn entry(&self) -> Option<AutostartEntry> {
+        self.imp().entry_object.borrow().as_ref().map(EntryObject::entry)
+    }
+
+    /// Show `entry` in place of the current one, e.g. after saving it.
+    ///
+    /// The other entries don't change, so they aren't loaded again. The file
+    /// monitor reloads them once the saved file is written anyway.
+    fn set_entry(&self, entry: AutostartEntry) {
+        let Some(entry_object) = self.imp().entry_object.borrow().clone() else {
+            return;
+        };
+
+        let previous = entry_object.entry();
+        let command_changed = previous.exec != entry.exec || previous.try_exec != entry.try_exec;
+        entry_object.set_entry(entry.clone(), entry_object.missing_program());
+        self.apply_window_filter();
+
+        if !command_changed {
+            return;
+        }
+
+        // Looking up the program blocks, just like loading the entries
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = row)] self,
+            #[weak] entry_object,
+            async move {
+                let lookup = entry.clone();
+                let Ok(missing_program) = gio::spawn_blocking(move || lookup.missing_program()).await else {
+                    return;
+                };
+
+                // The entry may have been reloaded in the meantime
+                if entry_object.entry() == entry {
+                    entry_object.set_entry(entry, missing_program);
+                    row.apply_window_filter();
+                }
+            }
+        ));
+    }
+
+    /// Filter the lists of the window again, as the entry may belong to
+    /// another list now, e.g. if it is broken
+    fn apply_window_filter(&self) {
+        if let Some(window) = self.root().and_downcast::<crate::window::BootMateWindow>() {
+            window.apply_filter();
+        }
+    }
+
+    /// Show the current state of the entry
+    fn update(&self) {
+        let Some(entry) = self.entry() else {
+            return;
+        };
+        let imp = self.imp();
+
+        // Escape markup characters in title and subtitle to prevent parsing errors
+        self.set_title(&glib::markup_escape_text(&entry.name));
+
+        // Set subtitle (exec command)
+        self.set_subtitle(&glib::markup_escape_text(&entry.exec));
+
+        // Show the comment as tooltip
+        self.set_tooltip_text(entry.comment.as_deref());
+
+        // Entries restricted to other desktops are never started in this session
+        let shown_in_desktop = entry.shown_in_current_desktop();
+
+        if imp.enable_switch.is_active() != entry.enabled {
+            imp.enable_switch.set_active(entry.enabled);
+        }
+        imp.enable_switch
+            .set_tooltip_text(Some(&Self::switch_tooltip(entry.enabled, shown_in_desktop)));
+
+        imp.icon
+            .set_icon_name(Some(entry.icon.as_deref().unwrap_or("application-x-executable")));
+
+        // Apply visual styling for disabled entries
+        if entry.is_effectively_enabled() {
+            self.set_opacity(1.0);
+            self.remove_css_class("dim-label");
+        } else {
+            self.set_opacity(0.5);
+            self.add_css_class("dim-label");
+        }
+
+        imp.desktop_indicator.set_visible(!shown_in_desktop);
+
+        if let Some(delay) = entry.delay {
+            imp.delay_label
+                .set_label(&gettext("%d s").replace("%d", &delay.to_string()));
+            imp.delay_label.set_tooltip_text(Some(
+                &gettext("Started %d seconds after login").replace("%d", &delay.to_string()),
+            ));
+        }
+        imp.delay_label.set_visible(entry.delay.is_some());
+
+        let missing_program = imp.entry_object.borrow().as_ref().and_then(EntryObject::missing_program);
+        imp.missing_label.set_tooltip_text(missing_program.as_deref());
+        imp.missing_label.set_visible(missing_program.is_some());
+
+        imp.overridden_label.set_visible(entry.is_override() && !entry.hidden);
+        imp.reset_button.set_visible(entry.is_override() && !entry.hidden);
+
+        // Hidden entries are deleted, they can only be restored
+        imp.enable_switch.set_visible(!entry.hidden);
+        imp.edit_button.set_visible(!entry.hidden);
+        imp.delete_button.set_visible(!entry.hidden);
+        imp.unhide_button.set_visible(entry.hidden);
+    }
+
+    /// Show a message in the window this row belongs to
+    fn report_message(&self, title: &str) {
+        if let Some(window) = self.root().and_downcast::<crate::window::BootMateWindow>() {
+            window.show_toast(title);
+        }
+    }
+
+    /// Tell about a change to the entry, offering to undo it
+    fn report_change(&self, title: &str, snapshot: Option<FileSnapshot>) {
+        if let Some(window) = self.root().and_downcast::<crate::window::BootMateWindow>() {
+            window.show_undo_toast(title, snapshot.into_iter().collect());
+        }
+    }
+
+    /// Show an error in the window this row belongs to
+    fn report_error(&self, error: &AutostartError) {
+        if let Some(window) = self.root().and_downcast::<crate::window::BootMateWindow>() {
+            window.show_autostart_error(error);
+        }
+    }
+
+    /// Show an error that occurred while saving `entry`, offering to resolve conflicts
+    fn report_save_error(&self, error: &AutostartError, entry: &AutostartEntry) {
+        if let Some(window) = self.root().and_downcast::<crate::window::BootMateWindow>() {
+            window.show_save_error(error, entry);
+        }
+    }
+
+    fn switch_tooltip(enabled: bool, shown_in_desktop: bool) -> String {
+        if !enabled {
+            gettext("Disabled")
+        } else if shown_in_desktop {
+            gettext("Enabled")
+        } else {
+            gettext("Enabled, but not started in this desktop environment")
+        }
+    }
+
     fn show_edit_dialog(&self, entry: &AutostartEntry) {
         let window = self.root().and_downcast::<gtk::Window>().unwrap();
 
@@ -187,16 +380,68 @@ impl EntryRow {
         dialog.set_default_response(Some("save"));
         dialog.set_close_response("cancel");
 
+        // Name and comment are edited in the current language
+        let name_row = adw::EntryRow::builder()
+            .title(gettext("Name"))
+            .text(&entry.name)
+            .build();
+
+        let comment_row = adw::EntryRow::builder()
+            .title(gettext("Comment"))
+            .text(entry.comment.as_deref().unwrap_or_default())
+            .build();
+
         // Create entry for exec command
         let entry_widget = adw::EntryRow::builder()
             .title(gettext("Command"))
             .text(&entry.exec)
             .build();
 
+        // Any delay a file may have must fit, or it would be clamped
+        let delay_row = adw::SpinRow::with_range(0.0, u32::MAX as f64, 1.0);
+        delay_row.set_title(&gettext("Start Delay"));
+        delay_row.set_subtitle(&gettext("Seconds to wait after login"));
+        delay_row.set_value(entry.delay.unwrap_or(0) as f64);
+
         let preferences_group = adw::PreferencesGroup::new();
+        preferences_group.add(&name_row);
+        preferences_group.add(&comment_row);
         preferences_group.add(&entry_widget);
+        preferences_group.add(&delay_row);
+
+        // Desktop environments the entry is started in
+        let current_desktops = current_desktops().join(";");
+        let desktops_group = adw::PreferencesGroup::builder()
+            .title(gettext("Desktop Environments"))
+            .description(if current_desktops.is_empty() {
+                gettext("Separate multiple desktops with semicolons, e.g. GNOME;KDE")
+            } else {
+                format!(
+                    "{} {}",
+                    gettext("Separate multiple desktops with semicolons, e.g. GNOME;KDE"),
+                    gettext("Current desktop: %s").replace("%s", &current_desktops)
+                )
+            })
+            .build();
+
+        let only_show_in_row = adw::EntryRow::builder()
+            .title(gettext("Only start in"))
+            .text(entry.only_show_in.join(";"))
+            .build();
+
+        let not_show_in_row = adw::EntryRow::builder()
+            .title(gettext("Never start in"))
+            .text(entry.not_show_in.join(";"))
+            .build();
 
-        dialog.set_extra_child(Some(&preferences_group));
+        desktops_group.add(&only_show_in_row);
+        desktops_group.add(&not_show_in_row);
+
+        let content_box = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        content_box.append(&preferences_group);
+        content_box.append(&desktops_group);
+
+        dialog.set_extra_child(Some(&content_box));
 
         let entry_clone = entry.clone();
         dialog.connect_response(
@@ -204,11 +449,152 @@ impl EntryRow {
             glib::clone!(
                 #[weak(rename_to = row)] self,
                 move |_, _| {
-                    let new_exec = entry_widget.text();
-                    if let Err(e) = entry_clone.save(&new_exec) {
-                        eprintln!("Failed to save entry: {}", e);
+                    let name = name_row.text();
+                    if name.is_empty() {
+                        row.report_message(&gettext("The name must not be empty"));
+                        return;
+                    }
+
+                    let comment = comment_row.text();
+                    let mut modified_entry = entry_clone.clone();
+                    modified_entry.name = name.to_string();
+                    modified_entry.comment = if comment.is_empty() {
+                        None
                     } else {
-                        // Refresh the list
+                        Some(comment.to_string())
+                    };
+                    modified_entry.exec = entry_widget.text().to_string();
+                    // Keep the key as it is unless the delay was changed
+                    let delay = delay_row.value() as u32;
+                    if delay != entry_clone.delay.unwrap_or(0) {
+                        modified_entry.delay = Some(delay).filter(|delay| *delay > 0);
+                    }
+                    modified_entry.only_show_in = Self::parse_desktop_list(&only_show_in_row.text());
+                    modified_entry.not_show_in = Self::parse_desktop_list(&not_show_in_row.text());
+
+                    // Only check the command if it was changed, so entries
+                    // with a command we can't parse can still be edited
+                    let validated = if modified_entry.exec != entry_clone.exec {
+                        modified_entry.validate()
+                    } else {
+                        Ok(())
+                    };
+                    let snapshot = modified_entry.snapshot().ok();
+                    match validated.and_then(|_| modified_entry.save()) {
+                        Ok(saved_entry) => {
+                            row.set_entry(saved_entry);
+                            row.report_change(
+                                &gettext("“%s” saved").replace("%s", &modified_entry.name),
+                                snapshot,
+                            );
+                        }
+                        Err(e) => row.report_save_error(&e, &modified_entry),
+                    }
+                }
+            ),
+        );
+
+        dialog.present(Some(&window));
+    }
+
+    /// Split the text of a desktop list row into desktop names
+    fn parse_desktop_list(text: &str) -> Vec<String> {
+        text.split(';')
+            .map(str::trim)
+            .filter(|desktop| !desktop.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Make a hidden entry visible again
+    fn unhide(&self, entry: &AutostartEntry) {
+        let snapshot = entry.snapshot().ok();
+        if let Err(e) = entry.unhide() {
+            self.report_error(&e);
+            return;
+        }
+
+        self.report_change(&gettext("“%s” restored").replace("%s", &entry.name), snapshot);
+        if let Some(window) = self.root().and_downcast::<crate::window::BootMateWindow>() {
+            window.load_autostart_entries();
+        }
+    }
+
+    /// Ask whether to remove the user's changes to a system entry, showing
+    /// what they are
+    fn show_reset_dialog(&self, entry: &AutostartEntry) {
+        let window = self.root().and_downcast::<gtk::Window>().unwrap();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Reset to System Default?"))
+            .body(gettext("Your changes to this entry are removed and the system entry is used again."))
+            .build();
+
+        dialog.add_response("cancel", &gettext("Cancel"));
+        dialog.add_response("reset", &gettext("Reset"));
+        dialog.set_response_appearance("reset", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        // Show the changed keys like a diff
+        if let Ok(changes) = entry.override_changes() {
+            if !changes.is_empty() {
+                let mut diff = String::new();
+                for change in &changes {
+                    if let Some(old_value) = &change.old_value {
+                        diff.push_str(&format!("- {}={}\n", change.key, old_value));
+                    }
+                    if let Some(new_value) = &change.new_value {
+                        diff.push_str(&format!("+ {}={}\n", change.key, new_value));
+                    }
+                }
+
+                let text_view = gtk::TextView::builder()
+                    .editable(false)
+                    .monospace(true)
+                    .wrap_mode(gtk::WrapMode::WordChar)
+                    .top_margin(6)
+                    .bottom_margin(6)
+                    .left_margin(6)
+                    .right_margin(6)
+                    .build();
+                text_view.buffer().set_text(diff.trim_end());
+
+                let scrolled_window = gtk::ScrolledWindow::builder()
+                    .child(&text_view)
+                    .min_content_height(120)
+                    .hscrollbar_policy(gtk::PolicyType::Never)
+                    .build();
+                scrolled_window.add_css_class("card");
+
+                let changes_label = gtk::Label::builder()
+                    .label(gettext("Your changes"))
+                    .xalign(0.0)
+                    .build();
+                changes_label.add_css_class("heading");
+
+                let changes_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+                changes_box.append(&changes_label);
+                changes_box.append(&scrolled_window);
+                dialog.set_extra_child(Some(&changes_box));
+            }
+        }
+
+        let entry_clone = entry.clone();
+        dialog.connect_response(
+            Some("reset"),
+            glib::clone!(
+                #[weak(rename_to = row)] self,
+                move |_, _| {
+                    let snapshot = entry_clone.snapshot().ok();
+                    if let Err(e) = entry_clone.reset_to_system_default() {
+                        row.report_error(&e);
+                    } else {
+                        row.report_change(
+                            &gettext("“%s” reset to system default").replace("%s", &entry_clone.name),
+                            snapshot,
+                        );
+                        // The system file the entry falls back to has to be read again
                         if let Some(window) = row.root().and_downcast::<crate::window::BootMateWindow>() {
                             window.load_autostart_entries();
                         }
@@ -225,12 +611,15 @@ impl EntryRow {
 
         let dialog = adw::AlertDialog::builder()
             .heading(gettext("Delete Autostart Entry?"))
-            .body(format!(
-                "{}",
-                gettext("This action cannot be undone.")
-            ))
             .build();
 
+        // System entries are hidden instead of deleting their files
+        if entry.is_removed_on_delete() {
+            dialog.set_body(&gettext("It can be restored from “Recently Removed” in the main menu."));
+        } else {
+            dialog.set_body(&gettext("It can be restored from the “Hidden” list."));
+        }
+
         dialog.add_response("cancel", &gettext("Cancel"));
         dialog.add_response("delete", &gettext("Delete"));
         dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
@@ -243,13 +632,19 @@ impl EntryRow {
             glib::clone!(
                 #[weak(rename_to = row)] self,
                 move |_, _| {
-                    if let Err(e) = entry_clone.delete() {
-                        eprintln!("Failed to delete entry: {}", e);
-                    } else {
-                        // Refresh the list
-                        if let Some(window) = row.root().and_downcast::<crate::window::BootMateWindow>() {
-                            window.load_autostart_entries();
+                    let snapshot = entry_clone.snapshot().ok();
+                    if let Err(e) = crate::window::BootMateWindow::delete_entry(&entry_clone) {
+                        row.report_error(&e);
+                    } else if let Some(window) = row.root().and_downcast::<crate::window::BootMateWindow>() {
+                        let title = gettext("“%s” deleted").replace("%s", &entry_clone.name);
+                        // Removed files are restored from the trash, not rewritten
+                        if entry_clone.is_removed_on_delete() {
+                            window.show_undo_removal_toast(&title, Vec::new(), vec![entry_clone.file_path.clone()]);
+                        } else {
+                            window.show_undo_toast(&title, snapshot.into_iter().collect());
                         }
+                        // Refresh the list
+                        window.load_autostart_entries();
                     }
                 }
             ),
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+use crate::autostart::{find_program, is_executable, resolve_symlinks, EntrySource};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where programs are looked up inside a root file system, whose `$PATH`
+/// isn't known
+const ROOT_PATH: &[&str] = &["/usr/local/sbin", "/usr/local/bin", "/usr/sbin", "/usr/bin", "/sbin", "/bin"];
+
+/// The base directories autostart entries are looked up in, following the
+/// XDG Base Directory specification.
+///
+/// All operations resolve their paths through this, so they can work on
+/// other directories than the ones of the current user, e.g. in tests. The
+/// directories returned by the methods are resolved with [`Self::resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    /// The user's configuration directory (`$XDG_CONFIG_HOME`)
+    pub config_home: PathBuf,
+    /// The user's data directory (`$XDG_DATA_HOME`)
+    pub data_home: PathBuf,
+    /// The system configuration directories (`$XDG_CONFIG_DIRS`), most important first
+    pub config_dirs: Vec<PathBuf>,
+    /// The system data directories (`$XDG_DATA_DIRS`), most important first
+    pub data_dirs: Vec<PathBuf>,
+    /// The root file system all directories are in, `None` for the running system
+    pub root: Option<PathBuf>,
+}
+
+/// Get the path of the absolute `path` inside `root`
+fn in_root(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Read an absolute path from an environment variable
+fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+/// Read a colon-separated list of absolute paths from an environment variable
+fn env_path_list(var: &str, default: &[&str]) -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = std::env::var(var)
+        .unwrap_or_default()
+        .split(':')
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .collect();
+
+    if dirs.is_empty() {
+        default.iter().map(PathBuf::from).collect()
+    } else {
+        dirs
+    }
+}
+
+impl Environment {
+    /// Get the directories of the current user from the XDG environment
+    /// variables, falling back to the defaults of the spec
+    pub fn from_env() -> Self {
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap_or_default());
+
+        Environment {
+            config_home: env_path("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config")),
+            data_home: env_path("XDG_DATA_HOME").unwrap_or_else(|| home.join(".local/share")),
+            config_dirs: env_path_list("XDG_CONFIG_DIRS", &["/etc/xdg"]),
+            data_dirs: env_path_list("XDG_DATA_DIRS", &["/usr/local/share", "/usr/share"]),
+            root: None,
+        }
+    }
+
+    /// Get the directories of a user of the system installed in `root`, e.g.
+    /// a mounted image or a chroot. `home` is the user's home directory as
+    /// seen from inside the root.
+    ///
+    /// The environment variables of the running system are ignored, the
+    /// defaults of the spec are used instead.
+    pub fn for_root(root: &Path, home: &Path) -> Self {
+        let home = in_root(root, home);
+
+        Environment {
+            config_home: home.join(".config"),
+            data_home: home.join(".local/share"),
+            config_dirs: vec![root.join("etc/xdg")],
+            data_dirs: vec![root.join("usr/local/share"), root.join("usr/share")],
+            root: Some(root.to_path_buf()),
+        }
+    }
+
+    /// Look up the home directory of `user` in the `/etc/passwd` of `root`
+    pub fn user_home_in_root(root: &Path, user: &str) -> Option<PathBuf> {
+        let passwd = fs::read_to_string(resolve_symlinks(&root.join("etc/passwd"), Some(root))).ok()?;
+        passwd.lines().find_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.len() >= 6 && fields[0] == user).then(|| PathBuf::from(fields[5]))
+        })
+    }
+
+    /// Get the path a file is actually read from and written to. Inside a
+    /// root file system, symlinks with absolute targets point into the root
+    /// rather than to the running system, so every part of the path is
+    /// resolved here before the system gets to follow them.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.root {
+            Some(root) => resolve_symlinks(path, Some(root)),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Look up a program like the session does, either as a path or in
+    /// `$PATH`. Inside a root file system, common directories of the root
+    /// are searched instead.
+    ///
+    /// Returns the path of the executable if it exists.
+    pub fn find_program(&self, program: &str) -> Option<PathBuf> {
+        let Some(root) = &self.root else {
+            return find_program(program);
+        };
+
+        if program.contains('/') {
+            let path = in_root(root, Path::new(program));
+            return is_executable(&path, Some(root)).then_some(path);
+        }
+
+        ROOT_PATH
+            .iter()
+            .map(|dir| in_root(root, Path::new(dir)).join(program))
+            .find(|path| is_executable(path, Some(root)))
+    }
+
+    /// Get the user's autostart directory
+    pub fn user_autostart_dir(&self) -> PathBuf {
+        self.resolve(&self.config_home.join("autostart"))
+    }
+
+    /// Get the directory of Boot Mate's own data of the user, e.g. removed entries
+    pub fn user_data_dir(&self) -> PathBuf {
+        self.resolve(&self.data_home.join("bootmate"))
+    }
+
+    /// Get the user's trash directory
+    pub fn user_trash_dir(&self) -> PathBuf {
+        self.resolve(&self.data_home.join("Trash"))
+    }
+
+    /// Get the system autostart directories, most important first.
+    ///
+    /// These are the `autostart` directories of `$XDG_CONFIG_DIRS` as required by
+    /// the autostart spec, followed by the legacy `gnome/autostart` directories
+    /// of `$XDG_DATA_DIRS` that GNOME still reads.
+    pub fn system_autostart_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self
+            .config_dirs
+            .iter()
+            .map(|dir| dir.join("autostart"))
+            .chain(self.data_dirs.iter().map(|dir| dir.join("gnome/autostart")))
+            .map(|dir| self.resolve(&dir))
+            .collect();
+
+        // The same directory may be listed more than once
+        let mut seen = HashSet::new();
+        dirs.retain(|dir| seen.insert(dir.clone()));
+        dirs
+    }
+
+    /// Get all autostart directories in order of precedence, starting with the
+    /// user's directory
+    pub fn autostart_dirs(&self) -> Vec<(PathBuf, EntrySource)> {
+        std::iter::once((self.user_autostart_dir(), EntrySource::User))
+            .chain(
+                self.system_autostart_dirs()
+                    .into_iter()
+                    .map(|dir| (dir, EntrySource::System)),
+            )
+            .collect()
+    }
+
+    /// Get the directories installed applications are looked up in, most
+    /// important first
+    pub fn applications_dirs(&self) -> Vec<PathBuf> {
+        self.data_dirs
+            .iter()
+            .map(|dir| self.resolve(&dir.join("applications")))
+            .collect()
+    }
+
+    /// Get the path for a new user entry, with a file name derived from its name
+    pub fn user_entry_path(&self, name: &str) -> PathBuf {
+        let filename = name
+            .to_lowercase()
+            .replace(" ", "-")
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '-')
+            .collect::<String>()
+            + ".desktop";
+
+        self.user_autostart_dir().join(filename)
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+use crate::autostart::{AutostartEntry, EntrySource, LoadProblem};
+use std::path::Path;
+
+/// Quote a string as a JSON string literal
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if u32::from(c) < 0x20 => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Paths that aren't valid UTF-8 are converted lossily
+fn quote_path(path: &Path) -> String {
+    quote(&path.to_string_lossy())
+}
+
+fn optional(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), quote)
+}
+
+fn string_array<S: AsRef<str>>(items: &[S]) -> String {
+    let items: Vec<String> = items.iter().map(|item| quote(item.as_ref())).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn source_name(source: EntrySource) -> &'static str {
+    match source {
+        EntrySource::User => "user",
+        EntrySource::System => "system",
+    }
+}
+
+/// Serialize an entry as a JSON object, with every line indented by `indent`
+pub fn entry_to_json(entry: &AutostartEntry, indent: &str) -> String {
+    let shadows: Vec<String> = entry
+        .shadows
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    let fields = [
+        ("id", quote(&entry.desktop_id())),
+        ("name", quote(&entry.name)),
+        ("exec", quote(&entry.exec)),
+        ("comment", optional(entry.comment.as_deref())),
+        ("icon", optional(entry.icon.as_deref())),
+        ("try_exec", optional(entry.try_exec.as_deref())),
+        ("path", quote_path(&entry.file_path)),
+        (
+            "directory",
+            entry.file_path.parent().map_or_else(|| "null".to_string(), quote_path),
+        ),
+        ("source", quote(source_name(entry.source))),
+        ("enabled", entry.enabled.to_string()),
+        ("hidden", entry.hidden.to_string()),
+        ("delay", entry.delay.map_or_else(|| "null".to_string(), |delay| delay.to_string())),
+        ("only_show_in", string_array(&entry.only_show_in)),
+        ("not_show_in", string_array(&entry.not_show_in)),
+        ("missing_program", optional(entry.missing_program().as_deref())),
+        ("overrides_system", entry.is_override().to_string()),
+        ("shadows", string_array(&shadows)),
+    ];
+
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}  {}: {}", indent, quote(key), value))
+        .collect();
+    format!("{{\n{}\n{}}}", fields.join(",\n"), indent)
+}
+
+/// Serialize a file that couldn't be loaded as a JSON object on one line
+pub fn problem_to_json(problem: &LoadProblem) -> String {
+    format!(
+        "{{\"path\": {}, \"source\": {}, \"error\": {}}}",
+        quote_path(&problem.path),
+        quote(source_name(problem.source)),
+        quote(&problem.error.to_string())
+    )
+}
+
+/// Serialize all entries and the files that couldn't be loaded as a JSON
+/// object with the arrays `entries` and `problems`
+pub fn to_json(entries: &[AutostartEntry], problems: &[LoadProblem]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("    {}", entry_to_json(entry, "    ")))
+        .collect();
+    let problems: Vec<String> = problems
+        .iter()
+        .map(|problem| format!("    {}", problem_to_json(problem)))
+        .collect();
+
+    let array = |items: Vec<String>| {
+        if items.is_empty() {
+            "[]".to_string()
+        } else {
+            format!("[\n{}\n  ]", items.join(",\n"))
+        }
+    };
+
+    format!(
+        "{{\n  \"entries\": {},\n  \"problems\": {}\n}}\n",
+        array(entries),
+        array(problems)
+    )
+}
@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Reading and changing autostart entries as described by the XDG Autostart
+//! and Desktop Entry specifications. This library doesn't depend on GTK, the
+//! graphical interface is only built with the `gui` feature.
+
+pub mod autostart;
+pub mod environment;
+pub mod json;
+pub mod removed;
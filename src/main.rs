@@ -1,19 +1,31 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
+#[cfg(feature = "gui")]
 mod application;
-mod autostart;
+mod cli;
 mod config;
+#[cfg(feature = "gui")]
+mod entry_object;
+#[cfg(feature = "gui")]
 mod entry_row;
+#[cfg(feature = "gui")]
 mod window;
 
+#[cfg(feature = "gui")]
 use application::BootMateApplication;
-use config::{APP_ID, GETTEXT_PACKAGE, LOCALEDIR};
+#[cfg(feature = "gui")]
+use config::APP_ID;
+use config::{GETTEXT_PACKAGE, LOCALEDIR};
 
+use bootmate::environment::Environment;
 use gettextrs::{bind_textdomain_codeset, bindtextdomain, setlocale, textdomain, LocaleCategory};
+#[cfg(feature = "gui")]
 use gtk::prelude::*;
-use gtk::{gio, glib};
+#[cfg(feature = "gui")]
+use gtk::gio;
+use std::process::ExitCode;
 
-fn main() -> glib::ExitCode {
+fn main() -> ExitCode {
     // Initialize locale from environment
     setlocale(LocaleCategory::LcAll, "");
 
@@ -57,11 +69,46 @@ fn main() -> glib::ExitCode {
         .expect("Failed to set text domain encoding");
     textdomain(GETTEXT_PACKAGE).expect("Failed to set text domain");
 
+    let args: Vec<String> = std::env::args().collect();
+    let (root_env, args) = match cli::parse_root_options(&args[1..]) {
+        Ok(result) => result,
+        Err(code) => return ExitCode::from(code),
+    };
+
+    // Commands are run without a display, e.g. over SSH
+    if args.first().is_some_and(|arg| cli::is_command(arg)) {
+        let env = root_env.unwrap_or_else(Environment::from_env);
+        return ExitCode::from(cli::run(&env, args));
+    }
+
+    run_gui(root_env, args)
+}
+
+/// Start the graphical interface. `args` are the remaining command line
+/// arguments, which are handled by GApplication.
+#[cfg(feature = "gui")]
+fn run_gui(root_env: Option<Environment>, args: &[String]) -> ExitCode {
     // Load resources
     gio::resources_register_include!("bootmate.gresource")
         .expect("Failed to register resources");
 
+    // A running instance for another root must not be reused
+    let flags = if root_env.is_some() {
+        gio::ApplicationFlags::NON_UNIQUE
+    } else {
+        gio::ApplicationFlags::default()
+    };
+
     // Create and run the application
-    let app = BootMateApplication::new(APP_ID, &gio::ApplicationFlags::default());
-    app.run()
+    let env = root_env.unwrap_or_else(Environment::from_env);
+    let app = BootMateApplication::new(APP_ID, &flags, env);
+    let program = std::env::args().next().unwrap_or_default();
+    ExitCode::from(app.run_with_args(&[&[program], args].concat()).get())
+}
+
+/// Built without the graphical interface, only commands can be run
+#[cfg(not(feature = "gui"))]
+fn run_gui(_root_env: Option<Environment>, _args: &[String]) -> ExitCode {
+    eprint!("{}", cli::usage());
+    ExitCode::from(cli::EXIT_USAGE)
 }
@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+use crate::autostart::{AutostartEntry, AutostartError, DesktopFile};
+use crate::environment::Environment;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TRASH_INFO_GROUP: &str = "Trash Info";
+
+/// Get the directory removed user entries are moved to when they can't be
+/// moved to the trash. It is laid out like a trash directory.
+pub fn removed_entries_dir(env: &Environment) -> PathBuf {
+    env.user_data_dir().join("removed")
+}
+
+/// Get the directories removed entries can be restored from
+fn trash_dirs(env: &Environment) -> Vec<PathBuf> {
+    vec![env.user_trash_dir(), removed_entries_dir(env)]
+}
+
+/// Percent-encode a path for the `Path` key of a trash info file
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Decode the percent-encoded `Path` key of a trash info file
+fn decode_path(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    PathBuf::from(OsStr::from_bytes(&decoded))
+}
+
+/// Format a point in time in local time like the `DeletionDate` key of a
+/// trash info file, as the trash does. Entries from both are sorted by it.
+fn format_deletion_date(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    let seconds = libc::time_t::try_from(seconds).unwrap_or(libc::time_t::MAX);
+
+    // SAFETY: `tm` is plain data, localtime_r only writes to it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&seconds, &mut tm) };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// Move a file, copying it if it is on another file system
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(e) = fs::rename(from, to) {
+        if fs::copy(from, to).is_err() {
+            return Err(e);
+        }
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Move a user entry file to the directory of removed entries, recording its
+/// original path so it can be restored
+pub fn move_to_removed(env: &Environment, path: &Path) -> Result<(), AutostartError> {
+    let dir = removed_entries_dir(env);
+    let files_dir = dir.join("files");
+    let info_dir = dir.join("info");
+
+    for dir in [&files_dir, &info_dir] {
+        fs::create_dir_all(dir).map_err(|e| AutostartError::io(dir, e))?;
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let info = format!(
+        "[{}]\nPath={}\nDeletionDate={}\n",
+        TRASH_INFO_GROUP,
+        encode_path(path),
+        format_deletion_date(SystemTime::now())
+    );
+
+    // Reserve a name that isn't used by an earlier removed file yet
+    let mut counter = 1;
+    let (name, info_path) = loop {
+        let name = if counter == 1 {
+            format!("{}{}", stem, extension)
+        } else {
+            format!("{}.{}{}", stem, counter, extension)
+        };
+        let info_path = info_dir.join(format!("{}.trashinfo", name));
+
+        match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(mut file) => {
+                file.write_all(info.as_bytes())
+                    .map_err(|e| AutostartError::io(&info_path, e))?;
+                break (name, info_path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(e) => return Err(AutostartError::io(&info_path, e)),
+        }
+    };
+
+    if let Err(e) = move_file(path, &files_dir.join(name)) {
+        let _ = fs::remove_file(&info_path);
+        return Err(AutostartError::io(path, e));
+    }
+
+    Ok(())
+}
+
+/// A user entry that was moved to the trash or the directory of removed entries
+#[derive(Debug, Clone)]
+pub struct RemovedEntry {
+    /// Name of the entry in the current language, or its file name if it can't be read
+    pub name: String,
+    /// Where the entry was removed from
+    pub original_path: PathBuf,
+    /// When the entry was removed, as `YYYY-MM-DDThh:mm:ss`
+    pub deletion_date: String,
+    /// Where the removed file is kept
+    pub file_path: PathBuf,
+    info_path: PathBuf,
+}
+
+impl RemovedEntry {
+    /// Get all removed user entries, most recently removed first
+    pub fn load_all(env: &Environment) -> Vec<Self> {
+        let user_autostart = env.user_autostart_dir();
+        let mut removed = Vec::new();
+
+        for dir in trash_dirs(env) {
+            let Ok(info_files) = fs::read_dir(dir.join("info")) else {
+                continue;
+            };
+
+            for info_file in info_files.flatten() {
+                let info_path = info_file.path();
+                let Some(name) = info_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".trashinfo"))
+                else {
+                    continue;
+                };
+
+                let Some(info) = fs::read_to_string(&info_path)
+                    .ok()
+                    .and_then(|content| DesktopFile::parse(&content).ok())
+                else {
+                    continue;
+                };
+
+                // Only entries of the user's autostart directory are of interest
+                let Some(original_path) = info.get(TRASH_INFO_GROUP, "Path").map(decode_path) else {
+                    continue;
+                };
+                if original_path.parent() != Some(user_autostart.as_path())
+                    || original_path.extension().and_then(|s| s.to_str()) != Some("desktop")
+                {
+                    continue;
+                }
+
+                let file_path = dir.join("files").join(name);
+                if !file_path.is_file() {
+                    continue;
+                }
+
+                let name = AutostartEntry::from_file(env, &file_path)
+                    .map(|entry| entry.name)
+                    .unwrap_or_else(|_| name.to_string());
+
+                removed.push(RemovedEntry {
+                    name,
+                    original_path,
+                    deletion_date: info
+                        .get(TRASH_INFO_GROUP, "DeletionDate")
+                        .unwrap_or_default()
+                        .to_string(),
+                    file_path,
+                    info_path,
+                });
+            }
+        }
+
+        removed.sort_by(|a, b| b.deletion_date.cmp(&a.deletion_date));
+        removed
+    }
+
+    /// Move the entry back to where it was removed from.
+    ///
+    /// Fails with [`AutostartError::NameCollision`] if a file with the same
+    /// name has been created there since.
+    pub fn restore(&self) -> Result<(), AutostartError> {
+        if self.original_path.exists() {
+            return Err(AutostartError::NameCollision {
+                path: self.original_path.clone(),
+            });
+        }
+
+        if let Some(dir) = self.original_path.parent() {
+            fs::create_dir_all(dir).map_err(|e| AutostartError::io(dir, e))?;
+        }
+
+        move_file(&self.file_path, &self.original_path)
+            .map_err(|e| AutostartError::io(&self.original_path, e))?;
+        let _ = fs::remove_file(&self.info_path);
+
+        Ok(())
+    }
+}
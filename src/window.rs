@@ -1,16 +1,52 @@
 
 // SPDX-License-Identifier: GPL-2.0-only
 
-use crate::autostart::{AutostartEntry, SandboxType};
+use bootmate::autostart::{
+    installed_applications, quote_exec_arg, AutostartEntry, AutostartError, DirectoryAccess,
+    EntrySource, FileSnapshot, LoadProblem, SandboxType,
+};
+use bootmate::environment::Environment;
+use crate::entry_object::EntryObject;
 use crate::entry_row::EntryRow;
+use bootmate::removed::{move_to_removed, RemovedEntry};
 use libadwaita as adw;
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gettextrs::gettext;
+use gettextrs::{gettext, ngettext};
 use glib::prelude::IsA;
 use gtk::{gio, glib};
-use std::cell::RefCell;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Duration;
+
+/// The lists of the window an entry can be shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryList {
+    User,
+    System,
+    Hidden,
+}
+
+impl EntryList {
+    fn for_entry(entry: &AutostartEntry) -> Self {
+        if entry.hidden {
+            EntryList::Hidden
+        } else if entry.source == EntrySource::System || entry.is_override() {
+            // User changes to system entries are shown with the system entries
+            EntryList::System
+        } else {
+            EntryList::User
+        }
+    }
+}
+
+/// Name and command of the installed applications, shared with the add dialog
+type Applications = Rc<Vec<(String, String)>>;
+
+/// How long to wait for more changes to autostart directories before reloading
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
 
 mod imp {
     use super::*;
@@ -18,6 +54,8 @@ mod imp {
     #[derive(Debug, Default, gtk::CompositeTemplate)]
     #[template(resource = "/ch/srueegger/bootmate/ui/window.ui")]
     pub struct BootMateWindow {
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
         #[template_child]
         pub header_bar: TemplateChild<adw::HeaderBar>,
         #[template_child]
@@ -29,6 +67,10 @@ mod imp {
         #[template_child]
         pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
         #[template_child]
+        pub problems_group: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub problems_list_box: TemplateChild<gtk::ListBox>,
+        #[template_child]
         pub user_group: TemplateChild<gtk::Box>,
         #[template_child]
         pub user_list_box: TemplateChild<gtk::ListBox>,
@@ -36,6 +78,26 @@ mod imp {
         pub system_group: TemplateChild<gtk::Box>,
         #[template_child]
         pub system_list_box: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub hidden_group: TemplateChild<gtk::Expander>,
+        #[template_child]
+        pub hidden_list_box: TemplateChild<gtk::ListBox>,
+        /// All loaded entries, shown by the user, system and hidden lists
+        pub entries: OnceCell<gio::ListStore>,
+        pub user_entries: OnceCell<gtk::FilterListModel>,
+        pub system_entries: OnceCell<gtk::FilterListModel>,
+        pub hidden_entries: OnceCell<gtk::FilterListModel>,
+        pub show_broken_only: Cell<bool>,
+        /// Whether the entries have been loaded once, until then a spinner is shown
+        pub loaded: Cell<bool>,
+        /// Incremented by every load, so only the result of the latest one is used
+        pub load_generation: Cell<u64>,
+        /// Name and command of installed applications, loaded when first needed
+        pub applications: RefCell<Option<Applications>>,
+        /// The directories entries are loaded from and saved to
+        pub environment: OnceCell<Environment>,
+        pub file_monitors: RefCell<Vec<gio::FileMonitor>>,
+        pub reload_source: RefCell<Option<glib::SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -58,7 +120,7 @@ mod imp {
             self.parent_constructed();
             let obj = self.obj();
             obj.setup_actions();
-            obj.load_autostart_entries();
+            obj.setup_models();
         }
     }
 
@@ -76,8 +138,25 @@ glib::wrapper! {
 }
 
 impl BootMateWindow {
-    pub fn new<P: IsA<adw::Application>>(app: &P) -> Self {
-        glib::Object::builder().property("application", app).build()
+    /// Create a window showing the entries of `env`
+    pub fn new<P: IsA<adw::Application>>(app: &P, env: Environment) -> Self {
+        let window: Self = glib::Object::builder().property("application", app).build();
+
+        // Make clear that another system is changed
+        if let Some(root) = &env.root {
+            let title = adw::WindowTitle::new(&gettext("Boot Mate"), &root.display().to_string());
+            window.imp().header_bar.set_title_widget(Some(&title));
+        }
+
+        window.imp().environment.set(env).expect("environment set twice");
+        window.setup_file_monitors();
+        window.load_autostart_entries();
+        window
+    }
+
+    /// Get the directories entries are loaded from and saved to
+    pub fn environment(&self) -> &Environment {
+        self.imp().environment.get().expect("environment is set in new()")
     }
 
     fn setup_actions(&self) {
@@ -93,65 +172,600 @@ impl BootMateWindow {
             })
             .build();
 
-        self.add_action_entries([action_refresh, action_add_entry]);
+        let action_show_broken = gio::ActionEntry::builder("show-broken")
+            .state(false.to_variant())
+            .activate(|window: &Self, action, _| {
+                let show_broken = !action
+                    .state()
+                    .and_then(|state| state.get::<bool>())
+                    .unwrap_or(false);
+                action.set_state(&show_broken.to_variant());
+                window.imp().show_broken_only.set(show_broken);
+                window.apply_filter();
+            })
+            .build();
+
+        let action_remove_broken = gio::ActionEntry::builder("remove-broken")
+            .activate(|window: &Self, _, _| {
+                window.show_remove_broken_dialog();
+            })
+            .build();
+
+        let action_show_removed = gio::ActionEntry::builder("show-removed")
+            .activate(|window: &Self, _, _| {
+                window.show_removed_dialog();
+            })
+            .build();
+
+        self.add_action_entries([
+            action_refresh,
+            action_add_entry,
+            action_show_broken,
+            action_remove_broken,
+            action_show_removed,
+        ]);
     }
 
-    pub fn load_autostart_entries(&self) {
+    /// Watch all autostart directories, so changes by other programs show up
+    fn setup_file_monitors(&self) {
+        let mut monitors = Vec::new();
+
+        for (dir, _) in self.environment().autostart_dirs() {
+            let monitor = match gio::File::for_path(&dir)
+                .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            {
+                Ok(monitor) => monitor,
+                Err(_) => {
+                    self.show_toast(
+                        &gettext("Changes to %s are not shown automatically")
+                            .replace("%s", &dir.display().to_string()),
+                    );
+                    continue;
+                }
+            };
+
+            monitor.connect_changed(glib::clone!(
+                #[weak(rename_to = window)] self,
+                move |_, file, other_file, event| {
+                    let is_desktop_file = |file: &gio::File| {
+                        file.path().is_some_and(|path| {
+                            path.extension().is_some_and(|ext| ext == "desktop")
+                        })
+                    };
+
+                    let relevant_event = matches!(
+                        event,
+                        gio::FileMonitorEvent::Created
+                            | gio::FileMonitorEvent::Deleted
+                            | gio::FileMonitorEvent::ChangesDoneHint
+                            | gio::FileMonitorEvent::Renamed
+                            | gio::FileMonitorEvent::MovedIn
+                            | gio::FileMonitorEvent::MovedOut
+                    );
+
+                    if relevant_event
+                        && (is_desktop_file(file) || other_file.is_some_and(is_desktop_file))
+                    {
+                        window.schedule_reload();
+                    }
+                }
+            ));
+
+            monitors.push(monitor);
+        }
+
+        self.imp().file_monitors.replace(monitors);
+    }
+
+    /// Reload the entries once the autostart directories stop changing, so
+    /// e.g. package upgrades touching many files only cause a single reload
+    fn schedule_reload(&self) {
+        let imp = self.imp();
+
+        if let Some(source) = imp.reload_source.take() {
+            source.remove();
+        }
+
+        let source = glib::timeout_add_local_once(
+            RELOAD_DELAY,
+            glib::clone!(
+                #[weak(rename_to = window)] self,
+                move || {
+                    window.imp().reload_source.take();
+                    window.load_autostart_entries();
+                }
+            ),
+        );
+        imp.reload_source.replace(Some(source));
+    }
+
+    /// Show the entries of the list store in the user, system and hidden lists
+    fn setup_models(&self) {
         let imp = self.imp();
+        let entries = gio::ListStore::new::<EntryObject>();
+
+        for (list, list_box, model_cell) in [
+            (EntryList::User, &*imp.user_list_box, &imp.user_entries),
+            (EntryList::System, &*imp.system_list_box, &imp.system_entries),
+            (EntryList::Hidden, &*imp.hidden_list_box, &imp.hidden_entries),
+        ] {
+            let filter = gtk::CustomFilter::new(glib::clone!(
+                #[weak(rename_to = window)] self,
+                #[upgrade_or] false,
+                move |item| {
+                    item.downcast_ref::<EntryObject>()
+                        .is_some_and(|entry_object| window.entry_matches_filter(entry_object, list))
+                }
+            ));
+            let model = gtk::FilterListModel::new(Some(entries.clone()), Some(filter));
+
+            list_box.bind_model(Some(&model), |item| {
+                let entry_object = item
+                    .downcast_ref::<EntryObject>()
+                    .expect("Entry list contains non-entry item");
+                EntryRow::new(entry_object).upcast()
+            });
+
+            model.connect_items_changed(glib::clone!(
+                #[weak(rename_to = window)] self,
+                move |_, _, _, _| window.update_visibility()
+            ));
 
-        // Check directory access and show warning if needed
-        self.check_sandbox_permissions();
+            model_cell.set(model).expect("Entry models set up twice");
+        }
+
+        imp.entries.set(entries).expect("Entry models set up twice");
+    }
+
+    fn entry_matches_filter(&self, entry_object: &EntryObject, list: EntryList) -> bool {
+        EntryList::for_entry(&entry_object.entry()) == list
+            && (!self.imp().show_broken_only.get() || entry_object.missing_program().is_some())
+    }
 
-        // Clear existing entries from both lists
-        while let Some(child) = imp.user_list_box.first_child() {
-            imp.user_list_box.remove(&child);
+    /// Filter the lists again, e.g. after entries changed
+    pub fn apply_filter(&self) {
+        let imp = self.imp();
+        for model in [
+            imp.user_entries.get(),
+            imp.system_entries.get(),
+            imp.hidden_entries.get(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(filter) = model.filter() {
+                filter.changed(gtk::FilterChange::Different);
+            }
         }
-        while let Some(child) = imp.system_list_box.first_child() {
-            imp.system_list_box.remove(&child);
+        self.update_visibility();
+    }
+
+    /// Show/hide groups and the empty state based on the visible rows
+    fn update_visibility(&self) {
+        let imp = self.imp();
+        if !imp.loaded.get() {
+            return;
         }
 
-        // Load autostart entries
-        let entries = AutostartEntry::load_all();
+        let user_count = imp.user_entries.get().map_or(0, |model| model.n_items());
+        let system_count = imp.system_entries.get().map_or(0, |model| model.n_items());
+        let hidden_count = imp.hidden_entries.get().map_or(0, |model| model.n_items());
+        let has_problems = imp.problems_list_box.first_child().is_some();
+
+        imp.problems_group.set_visible(has_problems);
+        imp.user_group.set_visible(user_count > 0);
+        imp.system_group.set_visible(system_count > 0);
+        imp.hidden_group.set_visible(hidden_count > 0);
 
-        if entries.is_empty() {
+        if user_count + system_count + hidden_count > 0 || has_problems {
+            imp.main_stack.set_visible_child_name("list");
+        } else if imp.show_broken_only.get() {
+            imp.status_page.set_title(&gettext("No Broken Entries"));
+            imp.status_page.set_description(Some(&gettext(
+                "The programs of all autostart entries are installed"
+            )));
+            imp.main_stack.set_visible_child_name("empty");
+        } else {
             imp.status_page.set_title(&gettext("No Autostart Entries"));
             imp.status_page.set_description(Some(&gettext(
                 "No applications are configured to start automatically"
             )));
             imp.main_stack.set_visible_child_name("empty");
-        } else {
-            // Separate entries into user and system
-            let mut user_entries = Vec::new();
-            let mut system_entries = Vec::new();
+        }
+    }
 
-            for entry in entries {
-                if entry.is_user_entry {
-                    user_entries.push(entry);
-                } else {
-                    system_entries.push(entry);
+    /// Reload the entries from disk in the background
+    pub fn load_autostart_entries(&self) {
+        let imp = self.imp();
+        let generation = imp.load_generation.get() + 1;
+        imp.load_generation.set(generation);
+        let env = self.environment().clone();
+
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = window)] self,
+            async move {
+                // Scanning the directories blocks, e.g. on network file systems,
+                // and so does looking up the programs in $PATH
+                let result = gio::spawn_blocking(move || {
+                    let (entries, problems) = AutostartEntry::load_all_with_problems(&env);
+                    let entries: Vec<(AutostartEntry, Option<String>)> = entries
+                        .into_iter()
+                        .map(|entry| {
+                            let missing_program = entry.missing_program();
+                            (entry, missing_program)
+                        })
+                        .collect();
+                    (AutostartEntry::check_directory_access(&env), (entries, problems))
+                })
+                .await;
+
+                // A newer load has been started in the meantime
+                if window.imp().load_generation.get() != generation {
+                    return;
+                }
+
+                match result {
+                    Ok((access, (entries, problems))) => {
+                        window.check_sandbox_permissions(&access);
+                        window.update_problems(problems);
+                        window.update_entries(entries);
+                    }
+                    Err(_) => window.show_toast(&gettext("Failed to load autostart entries")),
+                }
+            }
+        ));
+    }
+
+    /// Show the loaded entries. The list is updated in place, so rows of
+    /// unchanged entries keep their scroll position and keyboard focus.
+    fn update_entries(&self, entries: Vec<(AutostartEntry, Option<String>)>) {
+        let imp = self.imp();
+
+        let Some(store) = imp.entries.get() else {
+            return;
+        };
+        imp.loaded.set(true);
+
+        let ids: HashSet<String> = entries.iter().map(|(entry, _)| entry.desktop_id()).collect();
+
+        // Drop entries whose files are gone
+        store.retain(|item| {
+            item.downcast_ref::<EntryObject>()
+                .is_some_and(|entry_object| ids.contains(&entry_object.desktop_id()))
+        });
+
+        // Update the remaining entries and add new ones, in the order of `entries`
+        for (position, (entry, missing_program)) in (0..).zip(entries) {
+            let id = entry.desktop_id();
+            let existing = (position..store.n_items()).find_map(|i| {
+                store
+                    .item(i)
+                    .and_downcast::<EntryObject>()
+                    .filter(|entry_object| entry_object.desktop_id() == id)
+                    .map(|entry_object| (i, entry_object))
+            });
+
+            match existing {
+                Some((i, entry_object)) => {
+                    // Renamed entries move to their new position
+                    if i != position {
+                        store.remove(i);
+                        store.insert(position, &entry_object);
+                    }
+                    entry_object.set_entry(entry, missing_program);
                 }
+                None => store.insert(position, &EntryObject::new(entry, missing_program)),
             }
+        }
+
+        // Entries may have become user entries or broken
+        self.apply_filter();
+    }
+
+    /// Show the autostart files that couldn't be loaded
+    fn update_problems(&self, problems: Vec<LoadProblem>) {
+        let list_box = &self.imp().problems_list_box;
+        while let Some(row) = list_box.first_child() {
+            list_box.remove(&row);
+        }
 
-            // Add user entries to user list
-            for entry in &user_entries {
-                let row = EntryRow::new(entry);
-                imp.user_list_box.append(&row);
+        for problem in problems {
+            let file_name = problem
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&file_name))
+                .subtitle(glib::markup_escape_text(&problem.error.to_string()))
+                .build();
+
+            let icon = gtk::Image::from_icon_name("dialog-warning-symbolic");
+            icon.add_css_class("warning");
+            row.add_prefix(&icon);
+
+            let open_button = gtk::Button::builder()
+                .label(gettext("Open"))
+                .valign(gtk::Align::Center)
+                .build();
+            open_button.add_css_class("flat");
+            open_button.connect_clicked(glib::clone!(
+                #[weak(rename_to = window)] self,
+                #[strong(rename_to = path)] problem.path,
+                move |_| {
+                    let file = gio::File::for_path(window.environment().resolve(&path));
+                    let launcher = gtk::FileLauncher::new(Some(&file));
+                    launcher.launch(
+                        Some(&window),
+                        gio::Cancellable::NONE,
+                        glib::clone!(
+                            #[weak] window,
+                            move |result| {
+                                if let Err(e) = result {
+                                    if !e.matches(gtk::DialogError::Dismissed) {
+                                        window.show_error(&gettext("Error"), e.message());
+                                    }
+                                }
+                            }
+                        ),
+                    );
+                }
+            ));
+            row.add_suffix(&open_button);
+
+            if problem.is_repairable() {
+                let fix_button = gtk::Button::builder()
+                    .label(gettext("Fix"))
+                    .tooltip_text(gettext("Comment out invalid lines and add missing names"))
+                    .valign(gtk::Align::Center)
+                    .build();
+                fix_button.add_css_class("flat");
+                fix_button.connect_clicked(glib::clone!(
+                    #[weak(rename_to = window)] self,
+                    #[strong(rename_to = path)] problem.path,
+                    move |_| {
+                        let snapshot = FileSnapshot::take(&window.environment().resolve(&path));
+                        match AutostartEntry::repair(window.environment(), &path) {
+                            Ok(()) => window.show_undo_toast(
+                                &gettext("“%s” fixed").replace("%s", &file_name),
+                                snapshot.into_iter().collect(),
+                            ),
+                            Err(e) => window.show_autostart_error(&e),
+                        }
+                        window.load_autostart_entries();
+                    }
+                ));
+                row.add_suffix(&fix_button);
             }
 
-            // Add system entries to system list
-            for entry in &system_entries {
-                let row = EntryRow::new(entry);
-                imp.system_list_box.append(&row);
+            // System files can only be changed by an administrator
+            if problem.source == EntrySource::User {
+                let remove_button = gtk::Button::builder()
+                    .label(gettext("Remove"))
+                    .valign(gtk::Align::Center)
+                    .build();
+                remove_button.add_css_class("flat");
+                remove_button.connect_clicked(glib::clone!(
+                    #[weak(rename_to = window)] self,
+                    #[strong(rename_to = path)] problem.path,
+                    move |_| {
+                        if let Err(e) = Self::trash_file(window.environment(), &path) {
+                            window.show_autostart_error(&e);
+                        }
+                        window.load_autostart_entries();
+                    }
+                ));
+                row.add_suffix(&remove_button);
             }
 
-            // Show/hide groups based on whether they have entries
-            imp.user_group.set_visible(!user_entries.is_empty());
-            imp.system_group.set_visible(!system_entries.is_empty());
+            list_box.append(&row);
+        }
+    }
 
-            imp.main_stack.set_visible_child_name("list");
+    fn show_remove_broken_dialog(&self) {
+        let broken: Vec<AutostartEntry> = self
+            .imp()
+            .entries
+            .get()
+            .into_iter()
+            .flat_map(|store| store.iter::<EntryObject>().flatten())
+            .filter(|entry_object| entry_object.missing_program().is_some())
+            .map(|entry_object| entry_object.entry())
+            .filter(|entry| entry.is_user_entry() && !entry.hidden)
+            .collect();
+
+        if broken.is_empty() {
+            self.show_toast(&gettext("No Broken Entries"));
+            return;
+        }
+
+        let names: Vec<&str> = broken.iter().map(|entry| entry.name.as_str()).collect();
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Remove Broken Entries?"))
+            .body(format!(
+                "{}\n\n{}",
+                gettext("These user entries start programs that are not installed:"),
+                names.join("\n")
+            ))
+            .build();
+
+        dialog.add_response("cancel", &gettext("Cancel"));
+        dialog.add_response("remove", &gettext("Remove"));
+        dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        dialog.connect_response(
+            Some("remove"),
+            glib::clone!(
+                #[weak(rename_to = window)] self,
+                move |_, _| {
+                    let mut snapshots = Vec::new();
+                    let mut removed = Vec::new();
+
+                    for entry in &broken {
+                        let snapshot = entry.snapshot();
+                        match Self::delete_entry(entry) {
+                            Ok(()) if entry.is_removed_on_delete() => removed.push(entry.file_path.clone()),
+                            Ok(()) => snapshots.extend(snapshot.ok()),
+                            Err(e) => window.show_autostart_error(&e),
+                        }
+                    }
+
+                    let count = snapshots.len() + removed.len();
+                    if count > 0 {
+                        window.show_undo_removal_toast(
+                            &ngettext("Removed %d entry", "Removed %d entries", count as u32)
+                                .replace("%d", &count.to_string()),
+                            snapshots,
+                            removed,
+                        );
+                    }
+                    window.load_autostart_entries();
+                }
+            ),
+        );
+
+        dialog.present(Some(self));
+    }
+
+    /// Delete an entry, moving user files to the trash
+    pub fn delete_entry(entry: &AutostartEntry) -> Result<(), AutostartError> {
+        entry.delete_with(|path| Self::trash_file(entry.environment(), path))
+    }
+
+    /// Move a user file to the trash, so it can be restored
+    fn trash_file(env: &Environment, path: &Path) -> Result<(), AutostartError> {
+        // The trash of this system is no place for files of another one
+        if env.root.is_some() {
+            return move_to_removed(env, path);
+        }
+
+        match gio::File::for_path(path).trash(gio::Cancellable::NONE) {
+            Ok(()) => Ok(()),
+            // E.g. file systems without a trash directory
+            Err(e) if e.matches(gio::IOErrorEnum::NotSupported) => move_to_removed(env, path),
+            Err(e) if e.matches(gio::IOErrorEnum::PermissionDenied) => {
+                Err(AutostartError::PermissionDenied {
+                    path: path.to_path_buf(),
+                })
+            }
+            Err(e) => Err(AutostartError::Io {
+                path: path.to_path_buf(),
+                source: std::io::Error::other(e.message().to_string()),
+            }),
         }
     }
 
+    /// Show the user entries that were removed, allowing to restore them
+    fn show_removed_dialog(&self) {
+        let list_box = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .valign(gtk::Align::Start)
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .build();
+        list_box.add_css_class("boxed-list");
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vexpand(true)
+            .child(&list_box)
+            .build();
+
+        let empty_page = adw::StatusPage::builder()
+            .icon_name("user-trash-symbolic")
+            .title(gettext("No Recently Removed Entries"))
+            .build();
+
+        let spinner = gtk::Spinner::builder()
+            .spinning(true)
+            .halign(gtk::Align::Center)
+            .valign(gtk::Align::Center)
+            .width_request(32)
+            .height_request(32)
+            .build();
+
+        let stack = gtk::Stack::new();
+        stack.add_named(&spinner, Some("loading"));
+        stack.add_named(&scrolled_window, Some("list"));
+        stack.add_named(&empty_page, Some("empty"));
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&adw::HeaderBar::new());
+        toolbar_view.set_content(Some(&stack));
+
+        let dialog = adw::Dialog::builder()
+            .title(gettext("Recently Removed"))
+            .content_width(500)
+            .content_height(400)
+            .child(&toolbar_view)
+            .build();
+
+        let env = self.environment().clone();
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = window)] self,
+            #[weak] list_box,
+            #[weak] stack,
+            async move {
+                let removed = gio::spawn_blocking(move || RemovedEntry::load_all(&env))
+                    .await
+                    .unwrap_or_default();
+
+                for removed_entry in removed {
+                    let row = adw::ActionRow::builder()
+                        .title(glib::markup_escape_text(&removed_entry.name))
+                        .subtitle(glib::markup_escape_text(&format!(
+                            "{}\n{}",
+                            removed_entry.original_path.display(),
+                            removed_entry.deletion_date.replacen('T', " ", 1)
+                        )))
+                        .build();
+
+                    let restore_button = gtk::Button::builder()
+                        .label(gettext("Restore"))
+                        .valign(gtk::Align::Center)
+                        .build();
+                    restore_button.connect_clicked(glib::clone!(
+                        #[weak] window,
+                        #[weak] list_box,
+                        #[weak] stack,
+                        #[weak] row,
+                        move |_| {
+                            if let Err(e) = removed_entry.restore() {
+                                window.show_autostart_error(&e);
+                                return;
+                            }
+
+                            window.show_toast(
+                                &gettext("“%s” restored").replace("%s", &removed_entry.name),
+                            );
+                            window.load_autostart_entries();
+
+                            list_box.remove(&row);
+                            if list_box.first_child().is_none() {
+                                stack.set_visible_child_name("empty");
+                            }
+                        }
+                    ));
+                    row.add_suffix(&restore_button);
+                    list_box.append(&row);
+                }
+
+                stack.set_visible_child_name(if list_box.first_child().is_some() {
+                    "list"
+                } else {
+                    "empty"
+                });
+            }
+        ));
+
+        dialog.present(Some(self));
+    }
+
     fn show_add_entry_dialog(&self) {
         let dialog = adw::AlertDialog::builder()
             .heading(gettext("Add Autostart Entry"))
@@ -198,30 +812,20 @@ impl BootMateWindow {
 
         // Load available .desktop files from /usr/share/applications
         let programs = gtk::StringList::new(&[]);
-        let applications_dir = std::path::Path::new("/usr/share/applications");
         let app_commands: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
 
-        if let Ok(entries) = std::fs::read_dir(applications_dir) {
-            let mut app_list: Vec<(String, String)> = Vec::new();
-
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("desktop") {
-                    if let Ok(entry_data) = AutostartEntry::from_file(&path) {
-                        app_list.push((entry_data.name.clone(), entry_data.exec.clone()));
-                    }
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = window)] self,
+            #[weak] programs,
+            #[strong] app_commands,
+            async move {
+                // Add to the list and store commands
+                for (name, command) in window.applications().await.iter() {
+                    programs.append(name);
+                    app_commands.borrow_mut().push(command.clone());
                 }
             }
-
-            // Sort by name
-            app_list.sort_by(|a, b| a.0.cmp(&b.0));
-
-            // Add to the list and store commands
-            for (name, command) in &app_list {
-                programs.append(name);
-                app_commands.borrow_mut().push(command.clone());
-            }
-        }
+        ));
 
         program_row.set_model(Some(&programs));
 
@@ -273,7 +877,7 @@ impl BootMateWindow {
                         move |result| {
                             if let Ok(file) = result {
                                 if let Some(path) = file.path() {
-                                    command_row.set_text(&path.display().to_string());
+                                    command_row.set_text(&quote_exec_arg(&path.to_string_lossy()));
                                 }
                             }
                         }
@@ -293,63 +897,40 @@ impl BootMateWindow {
                 #[weak] program_row,
                 move |_, _| {
                     let name = name_row.text();
+                    if name.is_empty() {
+                        window.show_toast(&gettext("The name must not be empty"));
+                        return;
+                    }
+
                     let command = if use_program_check.is_active() {
                         // Get selected program command
                         let selected = program_row.selected();
-                        if selected == gtk::INVALID_LIST_POSITION {
-                            return;
-                        }
                         match app_commands.borrow().get(selected as usize) {
                             Some(cmd) => cmd.clone(),
-                            None => return,
+                            None => {
+                                window.show_toast(&gettext("No application selected"));
+                                return;
+                            }
                         }
                     } else {
                         command_row.text().to_string()
                     };
 
-                    if name.is_empty() || command.is_empty() {
-                        // Show error notification
+                    if command.is_empty() {
+                        window.show_toast(&gettext("The command must not be empty"));
                         return;
                     }
 
-                    // Generate a filename from the entry name
-                    let filename = name
-                        .to_lowercase()
-                        .replace(" ", "-")
-                        .chars()
-                        .filter(|c| c.is_alphanumeric() || *c == '-')
-                        .collect::<String>()
-                        + ".desktop";
-
-                    let user_dir = glib::user_config_dir();
-                    let file_path = user_dir.join("autostart").join(&filename);
-
-                    // Check if file already exists
-                    if file_path.exists() {
-                        let error_dialog = adw::AlertDialog::builder()
-                            .heading(gettext("Entry Already Exists"))
-                            .body(gettext("An autostart entry with this name already exists. Please choose a different name."))
-                            .build();
-                        error_dialog.add_response("ok", &gettext("OK"));
-                        error_dialog.set_default_response(Some("ok"));
-                        error_dialog.set_close_response("ok");
-                        error_dialog.present(Some(&window));
+                    // Create new autostart entry
+                    let entry = AutostartEntry::new(window.environment(), &name, &command);
+                    // The file name is made of the letters and digits of the name
+                    if entry.desktop_id() == ".desktop" {
+                        window.show_toast(&gettext("The name must contain a letter or digit"));
                         return;
                     }
 
-                    // Create new autostart entry
-                    let entry = AutostartEntry {
-                        name: name.to_string(),
-                        exec: command,
-                        icon: None,
-                        comment: None,
-                        enabled: true,
-                        file_path,
-                        is_user_entry: true,
-                    };
-
-                    if let Err(e) = entry.save(&entry.exec) {
-                        eprintln!("Failed to save entry: {}", e);
+                    if let Err(e) = entry.create() {
+                        window.show_autostart_error(&e);
                     } else {
                         window.load_autostart_entries();
                     }
@@ -360,14 +941,280 @@ impl BootMateWindow {
         dialog.present(Some(self));
     }
 
-    fn check_sandbox_permissions(&self) {
+    /// Get the installed applications, reading them in the background the
+    /// first time
+    async fn applications(&self) -> Applications {
+        if let Some(applications) = self.imp().applications.borrow().clone() {
+            return applications;
+        }
+
+        let env = self.environment().clone();
+        let applications = Rc::new(
+            gio::spawn_blocking(move || installed_applications(&env))
+                .await
+                .unwrap_or_default(),
+        );
+        self.imp().applications.replace(Some(applications.clone()));
+        applications
+    }
+
+    /// Show an error message in a dialog
+    pub fn show_error(&self, heading: &str, body: &str) {
+        let error_dialog = adw::AlertDialog::builder()
+            .heading(heading)
+            .body(body)
+            .build();
+        error_dialog.add_response("ok", &gettext("OK"));
+        error_dialog.set_default_response(Some("ok"));
+        error_dialog.set_close_response("ok");
+        error_dialog.present(Some(self));
+    }
+
+    /// Show a short message at the bottom of the window
+    pub fn show_toast(&self, title: &str) {
+        let toast = adw::Toast::builder()
+            .title(title)
+            .use_markup(false)
+            .build();
+        self.imp().toast_overlay.add_toast(toast);
+    }
+
+    /// Show a message about a change, offering to undo it by restoring the
+    /// files it touched
+    pub fn show_undo_toast(&self, title: &str, snapshots: Vec<FileSnapshot>) {
+        self.show_undo_removal_toast(title, snapshots, Vec::new());
+    }
+
+    /// Show a message about a change like [`Self::show_undo_toast`]. Undoing
+    /// it also moves the user files that were at `removed` back from the
+    /// trash, so they aren't listed as recently removed anymore.
+    pub fn show_undo_removal_toast(
+        &self,
+        title: &str,
+        snapshots: Vec<FileSnapshot>,
+        removed: Vec<PathBuf>,
+    ) {
+        if snapshots.is_empty() && removed.is_empty() {
+            self.show_toast(title);
+            return;
+        }
+
+        let toast = adw::Toast::builder()
+            .title(title)
+            .use_markup(false)
+            .button_label(gettext("_Undo"))
+            .priority(adw::ToastPriority::High)
+            .build();
+
+        toast.connect_button_clicked(glib::clone!(
+            #[weak(rename_to = window)] self,
+            move |_| {
+                for snapshot in &snapshots {
+                    if let Err(e) = snapshot.restore() {
+                        window.show_autostart_error(&e);
+                    }
+                }
+
+                if removed.is_empty() {
+                    window.load_autostart_entries();
+                    return;
+                }
+
+                let env = window.environment().clone();
+                let removed = removed.clone();
+                glib::spawn_future_local(glib::clone!(
+                    #[weak] window,
+                    async move {
+                        // Scanning the removed entries blocks like loading the list
+                        let errors = gio::spawn_blocking(move || {
+                            // The most recently removed file is the one to restore
+                            let removed_entries = RemovedEntry::load_all(&env);
+                            removed
+                                .iter()
+                                .filter_map(|path| {
+                                    match removed_entries.iter().find(|entry| &entry.original_path == path) {
+                                        Some(entry) => entry.restore().err(),
+                                        None => Some(AutostartError::io(
+                                            path,
+                                            std::io::ErrorKind::NotFound.into(),
+                                        )),
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .await
+                        .unwrap_or_default();
+
+                        for e in &errors {
+                            window.show_autostart_error(e);
+                        }
+                        window.load_autostart_entries();
+                    }
+                ));
+            }
+        ));
+
+        self.imp().toast_overlay.add_toast(toast);
+    }
+
+    /// Show an error of an autostart operation. The details explain what can
+    /// be done about it.
+    pub fn show_autostart_error(&self, error: &AutostartError) {
+        let (heading, body) = Self::describe_error(error);
+
+        let toast = adw::Toast::builder()
+            .title(&heading)
+            .use_markup(false)
+            .button_label(gettext("Details"))
+            .build();
+
+        toast.connect_button_clicked(glib::clone!(
+            #[weak(rename_to = window)] self,
+            move |_| window.show_error(&heading, &body)
+        ));
+
+        self.imp().toast_overlay.add_toast(toast);
+
+        if let AutostartError::Conflict { .. } = error {
+            self.load_autostart_entries();
+        }
+    }
+
+    /// Get a heading and an explanation for an error
+    fn describe_error(error: &AutostartError) -> (String, String) {
+        match error {
+            AutostartError::PermissionDenied { path } => {
+                let mut body = gettext("Boot Mate is not allowed to access this location.");
+                if AutostartEntry::detect_sandbox() == SandboxType::Flatpak {
+                    // Grant access to the directory of the file
+                    let dir = if path.is_dir() {
+                        path.as_path()
+                    } else {
+                        path.parent().unwrap_or(path)
+                    };
+                    body = format!(
+                        "{}\n\n{}\nflatpak override --user ch.srueegger.bootmate --filesystem={}",
+                        body,
+                        gettext("Run this command to grant access:"),
+                        dir.display()
+                    );
+                }
+                (gettext("Permission Denied"), format!("{}\n\n{}", error, body))
+            }
+            AutostartError::ReadOnlyFilesystem { .. } => (
+                gettext("Read-Only File System"),
+                format!("{}\n\n{}", error, gettext("The location can't be changed on this system.")),
+            ),
+            AutostartError::NameCollision { .. } => (
+                gettext("Entry Already Exists"),
+                gettext("An autostart entry with this name already exists. Please choose a different name."),
+            ),
+            AutostartError::InvalidExec { message } => {
+                (gettext("Invalid Command"), message.clone())
+            }
+            AutostartError::Parse { .. } | AutostartError::MissingKey { .. } => {
+                (gettext("Invalid Autostart File"), error.to_string())
+            }
+            AutostartError::Io { .. } => (gettext("Error"), error.to_string()),
+            AutostartError::Conflict { .. } => (
+                gettext("File Changed by Another Program"),
+                gettext("The autostart file was changed since Boot Mate loaded it. The list has been reloaded."),
+            ),
+        }
+    }
+
+    /// Show an error that occurred while saving `entry`, offering to resolve conflicts
+    pub fn show_save_error(&self, error: &AutostartError, entry: &AutostartEntry) {
+        if let AutostartError::Conflict { .. } = error {
+            self.show_conflict_dialog(entry);
+        } else {
+            self.show_autostart_error(error);
+        }
+    }
+
+    /// Let the user decide what to do when the file of `entry` was changed
+    /// by another program since it was loaded
+    fn show_conflict_dialog(&self, entry: &AutostartEntry) {
+        let merged = entry.merge_with_disk().ok();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("File Changed by Another Program"))
+            .body(gettext("The autostart file was changed since Boot Mate loaded it. Reload it to discard your changes, overwrite the other changes, or merge both."))
+            .build();
+
+        dialog.add_response("reload", &gettext("Reload"));
+        dialog.add_response("overwrite", &gettext("Overwrite"));
+        dialog.add_response("merge", &gettext("Merge"));
+        dialog.set_response_appearance("overwrite", adw::ResponseAppearance::Destructive);
+        dialog.set_response_appearance("merge", adw::ResponseAppearance::Suggested);
+        dialog.set_close_response("reload");
+
+        if let Some(merged) = &merged {
+            dialog.set_default_response(Some("merge"));
+
+            // Show what the merged file will look like
+            let text_view = gtk::TextView::builder()
+                .editable(false)
+                .monospace(true)
+                .wrap_mode(gtk::WrapMode::WordChar)
+                .top_margin(6)
+                .bottom_margin(6)
+                .left_margin(6)
+                .right_margin(6)
+                .build();
+            text_view.buffer().set_text(&merged.to_file_content());
+
+            let scrolled_window = gtk::ScrolledWindow::builder()
+                .child(&text_view)
+                .min_content_height(200)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .build();
+            scrolled_window.add_css_class("card");
+
+            let merged_label = gtk::Label::builder()
+                .label(gettext("Merged result"))
+                .xalign(0.0)
+                .build();
+            merged_label.add_css_class("heading");
+
+            let merged_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            merged_box.append(&merged_label);
+            merged_box.append(&scrolled_window);
+            dialog.set_extra_child(Some(&merged_box));
+        } else {
+            dialog.set_response_enabled("merge", false);
+            dialog.set_default_response(Some("reload"));
+        }
+
+        let entry = entry.clone();
+        dialog.connect_response(
+            None,
+            glib::clone!(
+                #[weak(rename_to = window)] self,
+                move |_, response| {
+                    let result = match (response, &merged) {
+                        ("overwrite", _) => entry.save_overwrite().map(drop),
+                        ("merge", Some(merged)) => merged.save().map(drop),
+                        _ => Ok(()),
+                    };
+
+                    if let Err(e) = result {
+                        window.show_autostart_error(&e);
+                    }
+                    window.load_autostart_entries();
+                }
+            ),
+        );
+
+        dialog.present(Some(self));
+    }
+
+    fn check_sandbox_permissions(&self, access: &DirectoryAccess) {
         let imp = self.imp();
-        let access = AutostartEntry::check_directory_access();
 
         // Check if we're missing any permissions
         let missing_permissions = !access.user_autostart
-            || !access.etc_xdg_autostart
-            || !access.usr_share_gnome_autostart
+            || access.system_autostart.iter().any(|(_, readable)| !readable)
             || !access.usr_share_applications;
 
         if missing_permissions && access.sandbox_type != SandboxType::None {
@@ -379,11 +1226,13 @@ impl BootMateWindow {
                     if !access.user_autostart {
                         msg.push_str("flatpak override --user ch.srueegger.bootmate --filesystem=xdg-config/autostart\n");
                     }
-                    if !access.etc_xdg_autostart {
-                        msg.push_str("flatpak override --user ch.srueegger.bootmate --filesystem=/etc/xdg/autostart:ro\n");
-                    }
-                    if !access.usr_share_gnome_autostart {
-                        msg.push_str("flatpak override --user ch.srueegger.bootmate --filesystem=/usr/share/gnome/autostart:ro\n");
+                    for (dir, readable) in &access.system_autostart {
+                        if !readable {
+                            msg.push_str(&format!(
+                                "flatpak override --user ch.srueegger.bootmate --filesystem={}:ro\n",
+                                dir.display()
+                            ));
+                        }
                     }
                     if !access.usr_share_applications {
                         msg.push_str("flatpak override --user ch.srueegger.bootmate --filesystem=/usr/share/applications:ro\n");
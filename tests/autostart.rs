@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+use bootmate::autostart::{installed_applications, AutostartEntry, AutostartError, EntrySource};
+use bootmate::environment::Environment;
+use bootmate::removed::RemovedEntry;
+use std::fs;
+use std::path::{Path, PathBuf};
+use temp_dir::TempDir;
+
+/// A home directory and system directories in a temporary directory
+struct Fixture {
+    _dir: TempDir,
+    env: Environment,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let env = Environment {
+            config_home: root.join("home/.config"),
+            data_home: root.join("home/.local/share"),
+            config_dirs: vec![root.join("etc/xdg-vendor"), root.join("etc/xdg")],
+            data_dirs: vec![root.join("usr/local/share"), root.join("usr/share")],
+            root: None,
+        };
+        Fixture { _dir: dir, env }
+    }
+
+    fn write(&self, path: &Path, content: &str) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        path.to_path_buf()
+    }
+
+    fn user_file(&self, id: &str, content: &str) -> PathBuf {
+        self.write(&self.env.user_autostart_dir().join(id), content)
+    }
+
+    /// Write a file to the autostart directory of the system config dir `index`
+    fn system_file(&self, index: usize, id: &str, content: &str) -> PathBuf {
+        self.write(&self.env.config_dirs[index].join("autostart").join(id), content)
+    }
+
+    fn load(&self) -> Vec<AutostartEntry> {
+        AutostartEntry::load_all(&self.env)
+    }
+
+    fn find(&self, id: &str) -> AutostartEntry {
+        self.load()
+            .into_iter()
+            .find(|entry| entry.desktop_id() == id)
+            .unwrap_or_else(|| panic!("{} not loaded", id))
+    }
+}
+
+fn desktop_file(name: &str, exec: &str) -> String {
+    format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\n", name, exec)
+}
+
+#[test]
+fn loads_user_and_system_entries() {
+    let fixture = Fixture::new();
+    fixture.user_file("user.desktop", &desktop_file("User App", "user-app"));
+    fixture.system_file(1, "system.desktop", &desktop_file("System App", "system-app"));
+    fixture.system_file(1, "README", "not an entry");
+
+    let entries = fixture.load();
+    assert_eq!(entries.len(), 2);
+
+    // Sorted by name
+    assert_eq!(entries[0].name, "System App");
+    assert_eq!(entries[0].source, EntrySource::System);
+    assert_eq!(entries[1].name, "User App");
+    assert_eq!(entries[1].source, EntrySource::User);
+    assert!(entries.iter().all(|entry| entry.enabled && !entry.hidden));
+}
+
+#[test]
+fn user_entry_takes_precedence_over_system_entry() {
+    let fixture = Fixture::new();
+    let system = fixture.system_file(1, "app.desktop", &desktop_file("System", "app"));
+    let user = fixture.user_file("app.desktop", &desktop_file("User", "app --user"));
+
+    let entries = fixture.load();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "User");
+    assert_eq!(entries[0].file_path, user);
+    assert_eq!(entries[0].shadows, vec![system]);
+    assert!(entries[0].is_override());
+}
+
+#[test]
+fn earlier_config_dir_takes_precedence() {
+    let fixture = Fixture::new();
+    let vendor = fixture.system_file(0, "app.desktop", &desktop_file("Vendor", "app"));
+    let distribution = fixture.system_file(1, "app.desktop", &desktop_file("Distribution", "app"));
+
+    let entry = fixture.find("app.desktop");
+    assert_eq!(entry.name, "Vendor");
+    assert_eq!(entry.file_path, vendor);
+    assert_eq!(entry.shadows, vec![distribution]);
+    assert!(!entry.is_override());
+}
+
+#[test]
+fn legacy_gnome_autostart_dir_comes_last() {
+    let fixture = Fixture::new();
+    let legacy = fixture.write(
+        &fixture.env.data_dirs[1].join("gnome/autostart/app.desktop"),
+        &desktop_file("Legacy", "app"),
+    );
+    fixture.write(
+        &fixture.env.data_dirs[1].join("gnome/autostart/legacy-only.desktop"),
+        &desktop_file("Legacy Only", "legacy"),
+    );
+    fixture.system_file(1, "app.desktop", &desktop_file("XDG", "app"));
+
+    let entry = fixture.find("app.desktop");
+    assert_eq!(entry.name, "XDG");
+    assert_eq!(entry.shadows, vec![legacy]);
+    assert_eq!(fixture.find("legacy-only.desktop").source, EntrySource::System);
+}
+
+#[test]
+fn invalid_user_file_hides_system_entry_and_is_reported() {
+    let fixture = Fixture::new();
+    fixture.system_file(1, "app.desktop", &desktop_file("System", "app"));
+    let broken = fixture.user_file("app.desktop", "not a desktop file\n");
+    fixture.user_file("nameless.desktop", "[Desktop Entry]\nExec=app\n");
+    fixture.user_file("only-type.desktop", "[Desktop Entry]\nType=Application\n");
+
+    let (entries, problems) = AutostartEntry::load_all_with_problems(&fixture.env);
+    assert!(entries.is_empty());
+    assert_eq!(problems.len(), 3);
+    assert_eq!(problems[0].path, broken);
+    assert!(matches!(problems[0].error, AutostartError::Parse { line: 1, .. }));
+    assert!(matches!(problems[1].error, AutostartError::MissingKey { key: "Name", .. }));
+    assert!(matches!(problems[2].error, AutostartError::MissingKey { key: "Name", .. }));
+
+    // Without a command, the repaired files still couldn't be loaded
+    assert!(!problems[0].is_repairable());
+    assert!(problems[1].is_repairable());
+    assert!(!problems[2].is_repairable());
+    assert!(AutostartEntry::repair(&fixture.env, &problems[2].path).is_err());
+
+    AutostartEntry::repair(&fixture.env, &problems[1].path).unwrap();
+    assert_eq!(fixture.find("nameless.desktop").name, "nameless");
+}
+
+#[test]
+fn disabling_system_entry_writes_override() {
+    let fixture = Fixture::new();
+    let content = desktop_file("App", "app");
+    let system = fixture.system_file(1, "app.desktop", &content);
+
+    let saved = fixture.find("app.desktop").set_enabled(false).unwrap();
+    assert_eq!(saved.file_path, fixture.env.user_autostart_dir().join("app.desktop"));
+    assert_eq!(saved.shadows, vec![system.clone()]);
+    assert!(saved.is_override());
+
+    // The system file is never changed
+    assert_eq!(fs::read_to_string(&system).unwrap(), content);
+
+    let entry = fixture.find("app.desktop");
+    assert!(!entry.enabled);
+    assert_eq!(entry, saved);
+
+    let changes = entry.override_changes().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].key, "X-GNOME-Autostart-enabled");
+    assert_eq!(changes[0].old_value, None);
+    assert_eq!(changes[0].new_value.as_deref(), Some("false"));
+}
+
+#[test]
+fn resetting_override_restores_system_entry() {
+    let fixture = Fixture::new();
+    let system = fixture.system_file(1, "app.desktop", &desktop_file("App", "app"));
+    fixture.find("app.desktop").set_enabled(false).unwrap();
+
+    fixture.find("app.desktop").reset_to_system_default().unwrap();
+
+    let entry = fixture.find("app.desktop");
+    assert!(entry.enabled);
+    assert_eq!(entry.file_path, system);
+    assert!(!fixture.env.user_autostart_dir().join("app.desktop").exists());
+}
+
+#[test]
+fn deleting_system_entry_hides_it() {
+    let fixture = Fixture::new();
+    let system = fixture.system_file(1, "app.desktop", &desktop_file("App", "app"));
+
+    fixture.find("app.desktop").delete().unwrap();
+
+    let entry = fixture.find("app.desktop");
+    assert!(entry.hidden);
+    assert!(entry.is_override());
+    assert!(!entry.is_effectively_enabled());
+    assert!(system.exists());
+
+    entry.unhide().unwrap();
+
+    let entry = fixture.find("app.desktop");
+    assert!(!entry.hidden);
+    assert_eq!(entry.file_path, system);
+}
+
+#[test]
+fn hide_stub_without_name_and_command_hides_system_entry() {
+    let fixture = Fixture::new();
+    let system = fixture.system_file(1, "app.desktop", &desktop_file("App", "app --start"));
+    let stub = fixture.user_file("app.desktop", "[Desktop Entry]\nHidden=true\n");
+
+    let (entries, problems) = AutostartEntry::load_all_with_problems(&fixture.env);
+    assert!(problems.is_empty());
+    assert_eq!(entries.len(), 1);
+
+    let entry = &entries[0];
+    assert!(entry.hidden);
+    assert!(entry.is_override());
+    assert_eq!(entry.name, "App");
+    assert_eq!(entry.exec, "app --start");
+    assert_eq!(entry.file_path, stub);
+
+    entry.unhide().unwrap();
+    let entry = fixture.find("app.desktop");
+    assert!(!entry.hidden);
+    assert_eq!(entry.file_path, system);
+}
+
+#[test]
+fn hidden_user_entry_can_be_unhidden() {
+    let fixture = Fixture::new();
+    fixture.user_file("app.desktop", &format!("{}Hidden=true\n", desktop_file("App", "app")));
+
+    let entry = fixture.find("app.desktop");
+    assert!(entry.hidden);
+    assert!(entry.enabled);
+    assert!(!entry.is_override());
+
+    entry.unhide().unwrap();
+    assert!(!fixture.find("app.desktop").hidden);
+}
+
+#[test]
+fn deleted_user_entry_can_be_restored() {
+    let fixture = Fixture::new();
+    let user = fixture.user_file("app.desktop", &desktop_file("App", "app"));
+
+    fixture.find("app.desktop").delete().unwrap();
+    assert!(!user.exists());
+    assert!(fixture.load().is_empty());
+
+    let removed = RemovedEntry::load_all(&fixture.env);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].name, "App");
+    assert_eq!(removed[0].original_path, user);
+
+    removed[0].restore().unwrap();
+    assert_eq!(fixture.find("app.desktop").name, "App");
+    assert!(RemovedEntry::load_all(&fixture.env).is_empty());
+}
+
+#[test]
+fn new_entries_are_created_in_user_dir() {
+    let fixture = Fixture::new();
+
+    let entry = AutostartEntry::new(&fixture.env, "My App", "my-app --start");
+    entry.create().unwrap();
+
+    let loaded = fixture.find("my-app.desktop");
+    assert_eq!(loaded.file_path, fixture.env.user_autostart_dir().join("my-app.desktop"));
+    assert_eq!(loaded.exec, "my-app --start");
+
+    let duplicate = AutostartEntry::new(&fixture.env, "My App", "other");
+    assert!(matches!(duplicate.create(), Err(AutostartError::NameCollision { .. })));
+}
+
+#[test]
+fn saving_fails_if_file_was_changed() {
+    let fixture = Fixture::new();
+    let user = fixture.user_file("app.desktop", &desktop_file("App", "app"));
+
+    let entry = fixture.find("app.desktop");
+    fs::write(&user, desktop_file("Changed", "app")).unwrap();
+
+    assert!(matches!(entry.set_enabled(false), Err(AutostartError::Conflict { .. })));
+    assert_eq!(fixture.find("app.desktop").name, "Changed");
+}
+
+#[test]
+fn override_fails_if_system_file_was_updated() {
+    let fixture = Fixture::new();
+    let system = fixture.system_file(1, "app.desktop", &desktop_file("App", "app --old"));
+
+    let entry = fixture.find("app.desktop");
+    fs::write(&system, desktop_file("App", "app --newer")).unwrap();
+
+    assert!(matches!(entry.set_enabled(false), Err(AutostartError::Conflict { .. })));
+    assert!(!fixture.env.user_autostart_dir().join("app.desktop").exists());
+
+    // Merging keeps the update
+    let merged = entry.merge_with_disk().unwrap();
+    assert_eq!(merged.exec, "app --newer");
+}
+
+#[test]
+fn installed_applications_are_read_from_data_dirs() {
+    let fixture = Fixture::new();
+    fixture.write(
+        &fixture.env.data_dirs[0].join("applications/editor.desktop"),
+        &desktop_file("Local Editor", "editor %F"),
+    );
+    fixture.write(
+        &fixture.env.data_dirs[1].join("applications/editor.desktop"),
+        &desktop_file("Editor", "editor"),
+    );
+    fixture.write(
+        &fixture.env.data_dirs[1].join("applications/browser.desktop"),
+        &desktop_file("Browser", "browser %u"),
+    );
+    // Not a valid command line, so it's offered as it is
+    fixture.write(
+        &fixture.env.data_dirs[1].join("applications/shell.desktop"),
+        &desktop_file("Shell", "sh -c 'echo hi' %U"),
+    );
+
+    assert_eq!(
+        installed_applications(&fixture.env),
+        vec![
+            ("Browser".to_string(), "browser".to_string()),
+            ("Local Editor".to_string(), "editor".to_string()),
+            ("Shell".to_string(), "sh -c 'echo hi' %U".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn root_file_system_is_used_for_links_and_programs() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    };
+
+    write("etc/passwd", "root:x:0:0::/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\n");
+    let home = Environment::user_home_in_root(root, "alice").unwrap();
+    assert_eq!(home, PathBuf::from("/home/alice"));
+    assert_eq!(Environment::user_home_in_root(root, "bob"), None);
+
+    let env = Environment::for_root(root, &home);
+    assert_eq!(env.user_autostart_dir(), root.join("home/alice/.config/autostart"));
+
+    // An absolute link points into the root, not to the running system
+    let target = write("usr/share/tool/tool.desktop", &desktop_file("Tool", "tool --start"));
+    let link = root.join("etc/xdg/autostart/tool.desktop");
+    fs::create_dir_all(link.parent().unwrap()).unwrap();
+    symlink("/usr/share/tool/tool.desktop", &link).unwrap();
+    assert_eq!(env.resolve(&link), target);
+
+    let entries = AutostartEntry::load_all(&env);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "Tool");
+    assert_eq!(entries[0].missing_program().as_deref(), Some("tool"));
+
+    let program = write("usr/bin/tool", "#!/bin/sh\n");
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(env.find_program("tool"), Some(program));
+    assert_eq!(entries[0].missing_program(), None);
+
+    // Changes are written to the user's directory inside the root
+    let saved = entries[0].set_enabled(false).unwrap();
+    assert_eq!(saved.file_path, env.user_autostart_dir().join("tool.desktop"));
+    assert!(saved.file_path.exists());
+}
+
+#[test]
+fn links_in_directories_stay_inside_root() {
+    use std::os::unix::fs::symlink;
+
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("rootfs");
+    let host = dir.path().join("host");
+    fs::create_dir_all(&host).unwrap();
+
+    // E.g. NixOS links /etc/xdg into /etc/static
+    fs::create_dir_all(root.join("etc/static/xdg/autostart")).unwrap();
+    fs::write(root.join("etc/static/xdg/autostart/app.desktop"), desktop_file("App", "app")).unwrap();
+    symlink("/etc/static/xdg", root.join("etc/xdg")).unwrap();
+    fs::write(root.join("etc/static/passwd"), "alice:x:1000:100::/home/alice:/bin/sh\n").unwrap();
+    symlink("/etc/static/passwd", root.join("etc/passwd")).unwrap();
+    assert_eq!(Environment::user_home_in_root(&root, "alice"), Some(PathBuf::from("/home/alice")));
+
+    // The absolute target exists on the host too, but must not be used
+    symlink(&host, root.join("home")).unwrap();
+    let home_in_root = root.join(host.strip_prefix("/").unwrap());
+    fs::create_dir_all(&home_in_root).unwrap();
+
+    let env = Environment::for_root(&root, Path::new("/home/alice"));
+    assert_eq!(env.user_autostart_dir(), home_in_root.join("alice/.config/autostart"));
+
+    let entry = AutostartEntry::load_all(&env).into_iter().next().unwrap();
+    assert_eq!(entry.name, "App");
+    entry.set_enabled(false).unwrap().delete().unwrap();
+    AutostartEntry::new(&env, "New", "new").create().unwrap();
+
+    assert!(home_in_root.join("alice/.config/autostart/app.desktop").exists());
+    assert!(home_in_root.join("alice/.config/autostart/new.desktop").exists());
+    assert_eq!(fs::read_dir(&host).unwrap().count(), 0);
+}